@@ -1,14 +1,17 @@
 //! CLI tool for CasperUSD smart contracts
 
-use casper_usd::stcspr::StCSPR;
+use casper_usd::stcspr::{StCSPR, StCSPRInitArgs};
 use casper_usd::stablecoin::CasperUSD;
 use casper_usd::oracle::PriceOracle;
-use casper_usd::trove_manager::TroveManager;
-use casper_usd::stability_pool::StabilityPool;
+use casper_usd::oracle_router::{OracleRouter, OracleRouterInitArgs};
+use casper_usd::trove_manager::{TroveManager, TroveManagerInitArgs};
+use casper_usd::stability_pool::{StabilityPool, StabilityPoolInitArgs};
+use odra::casper_types::{AsymmetricType, PublicKey};
 use odra::host::{HostEnv, NoArgs};
+use odra::prelude::Addressable;
 use odra_cli::{
     deploy::DeployScript,
-    ContractProvider, DeployedContractsContainer, DeployerExt,
+    DeployedContractsContainer, DeployerExt,
     OdraCli, 
 };
 
@@ -21,30 +24,79 @@ impl DeployScript for CasperUSDDeployScript {
         env: &HostEnv,
         container: &mut DeployedContractsContainer
     ) -> Result<(), odra_cli::deploy::Error> {
-        // Deploy stCSPR token
-        let _stcspr = StCSPR::load_or_deploy(
-            &env,
-            NoArgs,
+        // Deploy stCSPR token, delegating to the validator in STCSPR_VALIDATOR
+        let validator = std::env::var("STCSPR_VALIDATOR")
+            .ok()
+            .and_then(|hex| PublicKey::from_hex(hex).ok())
+            .ok_or_else(|| odra_cli::deploy::Error::OdraError {
+                message: String::from("STCSPR_VALIDATOR must be a validator public key (hex)"),
+            })?;
+        let stcspr = StCSPR::load_or_deploy(
+            env,
+            StCSPRInitArgs { validator },
             container,
             100_000_000_000
         )?;
 
         // Deploy cUSD stablecoin
-        let _cusd = CasperUSD::load_or_deploy(
-            &env,
+        let mut cusd = CasperUSD::load_or_deploy(
+            env,
             NoArgs,
             container,
             100_000_000_000
         )?;
 
         // Deploy Oracle
-        let _oracle = PriceOracle::load_or_deploy(
-            &env,
+        let oracle = PriceOracle::load_or_deploy(
+            env,
             NoArgs,
             container,
             100_000_000_000
         )?;
 
+        // Deploy the router over it - the same feed backs both sources
+        // until a second one is live
+        let router = OracleRouter::load_or_deploy(
+            env,
+            OracleRouterInitArgs {
+                primary: oracle.address(),
+                secondary: oracle.address(),
+                lst: stcspr.address(),
+            },
+            container,
+            100_000_000_000
+        )?;
+
+        // Deploy Trove Manager, pricing through the router
+        let mut trove_manager = TroveManager::load_or_deploy(
+            env,
+            TroveManagerInitArgs {
+                oracle: router.address(),
+                stablecoin: cusd.address(),
+                stcspr: stcspr.address(),
+            },
+            container,
+            200_000_000_000
+        )?;
+
+        // Deploy Stability Pool
+        let pool = StabilityPool::load_or_deploy(
+            env,
+            StabilityPoolInitArgs {
+                stablecoin: cusd.address(),
+                stcspr: stcspr.address(),
+                trove_manager: trove_manager.address(),
+            },
+            container,
+            200_000_000_000
+        )?;
+
+        // Wire them up: the pool absorbs liquidations, the manager mints cUSD
+        env.set_gas(10_000_000_000);
+        trove_manager.set_stability_pool(pool.address());
+        env.set_gas(10_000_000_000);
+        cusd.add_minter(trove_manager.address());
+
         Ok(())
     }
 }
@@ -56,6 +108,9 @@ pub fn main() {
         .contract::<StCSPR>()
         .contract::<CasperUSD>()
         .contract::<PriceOracle>()
+        .contract::<OracleRouter>()
+        .contract::<TroveManager>()
+        .contract::<StabilityPool>()
         .build()
         .run();
 }
@@ -0,0 +1,95 @@
+//! Aggregate Interest
+//!
+//! Liquity V2's "weighted recorded debt", so system-wide interest is known in O(1):
+//! - Tracks the sum of `debt * rate` over every trove's recorded debt
+//! - Pending interest is that sum times the time since the last accrual
+//! - Rounded up, while single troves round down, so the aggregate never
+//!   falls short of the troves it covers
+
+use odra::casper_types::U256;
+use odra::prelude::*;
+
+use crate::math::{mul_div, Rounding, DECIMALS};
+
+pub const SECONDS_PER_YEAR: u64 = 31536000;
+
+/// Aggregate interest errors (codes 460-469)
+#[odra::odra_error]
+pub enum Error {
+    MathOverflow = 460,
+}
+
+#[odra::module(errors = Error)]
+pub struct AggregateInterest {
+    // Sum of recorded debt * annual rate (9 decimals) over all troves
+    weighted_debt_sum: Var<U256>,
+    // Time interest was last folded into the recorded total
+    last_update: Var<u64>,
+}
+
+#[odra::module]
+impl AggregateInterest {
+    /// Interest accrued by all troves since the last accrual
+    pub fn pending_interest(&self, now: u64) -> U256 {
+        let elapsed = now.saturating_sub(self.last_update.get_or_default());
+        let weighted = self.weighted_debt_sum.get_or_default();
+        if elapsed == 0 || weighted.is_zero() {
+            return U256::zero();
+        }
+        mul_div(weighted, U256::from(elapsed), U256::from(DECIMALS * SECONDS_PER_YEAR), Rounding::Up)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    /// Close the current accrual period, returning the interest it earned
+    pub fn accrue(&mut self, now: u64) -> U256 {
+        let interest = self.pending_interest(now);
+        self.last_update.set(now);
+        interest
+    }
+
+    /// Replace a trove's old weighted debt with its new one
+    ///
+    /// Call `accrue` first - the old weight has earned interest up to now.
+    pub fn update_weight(&mut self, old_debt: U256, old_rate: u64, new_debt: U256, new_rate: u64) {
+        let sum = old_debt
+            .checked_mul(old_rate.into())
+            .and_then(|old| self.weighted_debt_sum.get_or_default().checked_sub(old))
+            .zip(new_debt.checked_mul(new_rate.into()))
+            .and_then(|(rest, new)| rest.checked_add(new))
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        self.weighted_debt_sum.set(sum);
+    }
+}
+
+/// Interest a single trove's debt earns over `elapsed` seconds, rounded down
+pub fn trove_interest(debt: U256, rate: u64, elapsed: u64) -> Option<U256> {
+    mul_div(debt, U256::from(rate) * elapsed, U256::from(DECIMALS * SECONDS_PER_YEAR), Rounding::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, NoArgs};
+
+    #[test]
+    fn weights_that_do_not_add_up_revert_with_their_own_code() {
+        let env = odra_test::env();
+        let mut interest = AggregateInterest::deploy(&env, NoArgs);
+        interest.update_weight(U256::zero(), 0, U256::from(1_000), DECIMALS / 10);
+        
+        // Removing more weight than was ever added
+        let err = interest
+            .try_update_weight(U256::from(2_000), DECIMALS / 10, U256::zero(), 0)
+            .unwrap_err();
+        assert_eq!(err, Error::MathOverflow.into());
+        assert_eq!(err.code(), 460);
+        assert_eq!(
+            interest.try_update_weight(U256::zero(), 0, U256::MAX, DECIMALS),
+            Err(Error::MathOverflow.into())
+        );
+        
+        // A year on the 1,000 left at 10%
+        let now = env.block_time_secs() + SECONDS_PER_YEAR;
+        assert_eq!(interest.pending_interest(now), U256::from(100));
+    }
+}
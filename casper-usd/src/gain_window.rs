@@ -0,0 +1,87 @@
+//! Trailing Gain Window
+//!
+//! The Stability Pool's per-unit gains, bucketed by day, for its trailing APY:
+//! - Keeps a running sum over the last `APY_WINDOW_DAYS` days
+//! - Days that fall out of the window are subtracted as it moves forward,
+//!   so reading it right after a gain is recorded costs O(1)
+
+use odra::casper_types::U256;
+use odra::prelude::*;
+
+pub const APY_WINDOW_DAYS: u64 = 30;
+
+#[odra::module]
+pub struct GainWindow {
+    // Per-unit gains (18 decimals), by day
+    daily_gains: Mapping<u64, U256>,
+    // Sum of the daily gains from `first_day` on
+    sum: Var<U256>,
+    first_day: Var<u64>,
+}
+
+#[odra::module]
+impl GainWindow {
+    /// Add `gain` to `day`'s bucket, moving the window up to it
+    pub fn record(&mut self, day: u64, gain: U256) {
+        let (first_day, sum) = self.window_at(day);
+        self.first_day.set(first_day);
+        self.sum.set(sum + gain);
+        let gains = self.daily_gains.get(&day).unwrap_or_default();
+        self.daily_gains.set(&day, gains + gain);
+    }
+
+    /// Gains over the `APY_WINDOW_DAYS` days up to `today`, today included
+    pub fn sum(&self, today: u64) -> U256 {
+        self.window_at(today).1
+    }
+
+    pub fn daily_gain(&self, day: u64) -> U256 {
+        self.daily_gains.get(&day).unwrap_or_default()
+    }
+
+    /// First day and sum of the window ending at `today`
+    /// 
+    /// Gains are only recorded inside the stored window, so once it has
+    /// moved a full window on, nothing in it is left.
+    fn window_at(&self, today: u64) -> (u64, U256) {
+        let first_day = today.saturating_sub(APY_WINDOW_DAYS - 1);
+        let mut day = self.first_day.get_or_default();
+        if first_day >= day + APY_WINDOW_DAYS {
+            return (first_day, U256::zero());
+        }
+        let mut sum = self.sum.get_or_default();
+        while day < first_day {
+            sum -= self.daily_gain(day);
+            day += 1;
+        }
+        (first_day.max(day), sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, NoArgs};
+
+    #[test]
+    fn running_sum_matches_the_buckets_in_the_window() {
+        let env = odra_test::env();
+        let mut window = GainWindow::deploy(&env, NoArgs);
+        let start = 20_000;
+        for (offset, gain) in [(0, 5u64), (3, 7), (3, 1), (29, 2), (31, 4)] {
+            window.record(start + offset, U256::from(gain));
+        }
+
+        // Day 31's window starts at day 2: the first bucket is gone
+        assert_eq!(window.sum(start + 31), U256::from(14));
+        assert_eq!(window.daily_gain(start + 3), U256::from(8));
+        // Reading ahead drops buckets without moving the stored window
+        assert_eq!(window.sum(start + 33), U256::from(6));
+        assert_eq!(window.sum(start + 60), U256::from(4));
+        assert!(window.sum(start + 61).is_zero());
+
+        // A gain after a long gap starts the sum over
+        window.record(start + 100, U256::from(3));
+        assert_eq!(window.sum(start + 100), U256::from(3));
+    }
+}
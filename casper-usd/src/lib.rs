@@ -11,7 +11,15 @@ extern crate alloc;
 // - Redemption mechanism for peg stability
 
 pub mod oracle;
+pub mod oracle_router;
 pub mod stablecoin;
 pub mod trove_manager;
+pub mod redistribution;
+pub mod aggregate_interest;
+pub mod sorted_troves;
 pub mod stability_pool;
+pub mod gain_window;
+pub mod stcspr;
 pub mod mock_stcspr;
+pub mod token;
+pub mod math;
@@ -0,0 +1,182 @@
+//! Fixed-Point Math
+//!
+//! Shared arithmetic for all contracts:
+//! - Prices, rates and fees are decimals with 9 decimals (`DECIMALS`)
+//! - `mul_div` keeps a 512-bit intermediate, so `a * b` never overflows
+//! - Every rounding step is explicit - callers round in the protocol's favour
+//! - Nothing here panics: results that don't fit come back as `None`, for
+//!   callers to revert with their own error
+
+use odra::casper_types::{U256, U512};
+
+/// One whole unit: 9 decimals, same as cUSD and stCSPR
+pub const DECIMALS: u64 = 1_000_000_000;
+
+/// Direction to round a division result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// `a * b / denominator`, rounded as asked
+///
+/// `None` when the denominator is zero or the result doesn't fit in a U256.
+pub fn mul_div(a: U256, b: U256, denominator: U256, rounding: Rounding) -> Option<U256> {
+    if denominator.is_zero() { return None; }
+    let product = widen(a) * widen(b);
+    let denominator = widen(denominator);
+    let mut result = product / denominator;
+    if rounding == Rounding::Up && !(product % denominator).is_zero() {
+        result += U512::one();
+    }
+    narrow(result)
+}
+
+/// `amount * factor`, for a decimal `factor` (price, rate, fee)
+pub fn dec_mul(amount: U256, factor: impl Into<U256>, rounding: Rounding) -> Option<U256> {
+    mul_div(amount, factor.into(), U256::from(DECIMALS), rounding)
+}
+
+/// `amount / divisor`, for a decimal `divisor` (price, rate, fee)
+pub fn dec_div(amount: U256, divisor: impl Into<U256>, rounding: Rounding) -> Option<U256> {
+    mul_div(amount, U256::from(DECIMALS), divisor.into(), rounding)
+}
+
+/// `percent`% of `amount`
+pub fn percent_of(amount: U256, percent: u64, rounding: Rounding) -> Option<U256> {
+    mul_div(amount, U256::from(percent), U256::from(100), rounding)
+}
+
+/// `numerator / denominator` in whole percent, rounded down
+///
+/// Unbounded (`U256::MAX`) when the denominator is zero, e.g. a debt-free
+/// trove, and saturates there when the ratio is too large to represent.
+pub fn ratio_percent(numerator: U256, denominator: U256) -> U256 {
+    mul_div(numerator, U256::from(100), denominator, Rounding::Down).unwrap_or(U256::MAX)
+}
+
+/// Decimal number with 9 decimals, e.g. the redemption base rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(U256);
+
+impl Decimal {
+    pub fn one() -> Self {
+        Decimal(U256::from(DECIMALS))
+    }
+
+    /// From a raw value already scaled by `DECIMALS`
+    pub fn from_raw(raw: impl Into<U256>) -> Self {
+        Decimal(raw.into())
+    }
+
+    /// `numerator / denominator` as a decimal
+    pub fn from_ratio(numerator: U256, denominator: U256, rounding: Rounding) -> Option<Self> {
+        dec_div(numerator, denominator, rounding).map(Decimal)
+    }
+
+    /// Raw value scaled by `DECIMALS`
+    pub fn raw(self) -> U256 {
+        self.0
+    }
+
+    /// Raw value as u64, for rates stored that way
+    pub fn raw_u64(self) -> u64 {
+        self.0.as_u64()
+    }
+
+    pub fn mul(self, other: Decimal, rounding: Rounding) -> Option<Decimal> {
+        dec_mul(self.0, other.0, rounding).map(Decimal)
+    }
+}
+
+/// `base ^ n` by exponentiation by squaring, rounding half up at each step
+///
+/// Meant for decay factors below one: `n` is capped at ~1000 years of
+/// minutes, by which point the result is effectively zero. `None` if a
+/// base above one overflows.
+pub fn dec_pow(base: Decimal, n: u64) -> Option<Decimal> {
+    let mut n = if n > 525_600_000 { 525_600_000 } else { n };
+    if n == 0 { return Some(Decimal::one()); }
+
+    let mut x = base;
+    let mut y = Decimal::one();
+    while n > 1 {
+        if n % 2 == 0 {
+            x = mul_half_up(x, x)?;
+            n /= 2;
+        } else {
+            y = mul_half_up(x, y)?;
+            x = mul_half_up(x, x)?;
+            n = (n - 1) / 2;
+        }
+    }
+    mul_half_up(x, y)
+}
+
+/// Liquity's `decMul`: unbiased rounding so repeated squaring doesn't drift
+fn mul_half_up(x: Decimal, y: Decimal) -> Option<Decimal> {
+    let product = widen(x.0) * widen(y.0) + U512::from(DECIMALS / 2);
+    narrow(product / U512::from(DECIMALS)).map(Decimal)
+}
+
+/// U256 -> U512, e.g. for native CSPR amounts
+pub fn widen(value: U256) -> U512 {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    U512::from_little_endian(&bytes)
+}
+
+/// U512 -> U256, `None` if it doesn't fit
+pub fn narrow(value: U512) -> Option<U256> {
+    let mut bytes = [0u8; 64];
+    value.to_little_endian(&mut bytes);
+    if bytes[32..].iter().any(|b| *b != 0) { return None; }
+    Some(U256::from_little_endian(&bytes[..32]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_rounds_in_the_requested_direction() {
+        let (a, b, d) = (U256::from(10), U256::from(10), U256::from(3));
+        assert_eq!(mul_div(a, b, d, Rounding::Down), Some(U256::from(33)));
+        assert_eq!(mul_div(a, b, d, Rounding::Up), Some(U256::from(34)));
+        assert_eq!(mul_div(a, U256::from(3), d, Rounding::Up), Some(U256::from(10)));
+    }
+
+    #[test]
+    fn mul_div_survives_intermediate_overflow() {
+        let half_max = U256::MAX / 2;
+        assert_eq!(mul_div(half_max, U256::from(4), U256::from(4), Rounding::Down), Some(half_max));
+    }
+
+    #[test]
+    fn mul_div_reports_what_it_cannot_compute() {
+        let one = U256::one();
+        assert_eq!(mul_div(one, one, U256::zero(), Rounding::Down), None);
+        assert_eq!(mul_div(U256::MAX, U256::from(2), one, Rounding::Down), None);
+        assert_eq!(narrow(widen(U256::MAX) + U512::one()), None);
+        assert_eq!(dec_pow(Decimal::from_raw(U256::MAX), 2), None);
+    }
+
+    #[test]
+    fn percentages() {
+        let amount = U256::from(1_000);
+        assert_eq!(percent_of(amount, 110, Rounding::Down), Some(U256::from(1_100)));
+        assert_eq!(ratio_percent(U256::from(150), U256::from(100)), U256::from(150));
+        assert_eq!(ratio_percent(U256::from(150), U256::zero()), U256::MAX);
+        assert_eq!(ratio_percent(U256::MAX, U256::one()), U256::MAX);
+    }
+
+    #[test]
+    fn dec_pow_matches_half_life() {
+        // 12h of minute decay halves the base rate
+        let decay = dec_pow(Decimal::from_raw(999_037_759u64), 720).unwrap();
+        let half = Decimal::from_raw(DECIMALS / 2);
+        assert!(decay.raw() > half.raw() - 1_000 && decay.raw() < half.raw() + 1_000);
+        assert_eq!(dec_pow(decay, 0), Some(Decimal::one()));
+    }
+}
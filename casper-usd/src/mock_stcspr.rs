@@ -1,16 +1,37 @@
 //! Mock stCSPR Token for Testnet - CEP-18 compatible with faucet
+//! 
+//! Test double for `stcspr::StCSPR`: no staking, just a faucet and an
+//! LST-style `exchange_rate` (CSPR per stCSPR) the owner can set directly
+//! or grow with `accrue_rewards`.
+use odra::casper_types::U256;
 use odra::prelude::*;
 
+use crate::math::{mul_div, Rounding, DECIMALS};
+
 const FAUCET_AMOUNT: u64 = 10_000_000_000_000; // 10,000 with 9 decimals
 
-#[odra::module]
+/// Same codes as the CEP-18 standard
+#[odra::odra_error]
+pub enum Error {
+    InsufficientBalance = 60_001,
+    InsufficientAllowance = 60_002,
+    InsufficientRights = 60_010,
+    InvalidExchangeRate = 60_100,
+    NoSupply = 60_101,
+    MathOverflow = 60_102,
+}
+
+#[odra::module(errors = Error)]
 pub struct MockStCSPR {
     name: Var<String>,
     symbol: Var<String>,
     decimals: Var<u8>,
-    total_supply: Var<u64>,
-    balances: Mapping<Address, u64>,
-    allowances: Mapping<(Address, Address), u64>,
+    total_supply: Var<U256>,
+    balances: Mapping<Address, U256>,
+    allowances: Mapping<(Address, Address), U256>,
+    owner: Var<Address>,
+    // CSPR per stCSPR, 9 decimals
+    exchange_rate: Var<U256>,
 }
 
 #[odra::module]
@@ -19,18 +40,46 @@ impl MockStCSPR {
         self.name.set(String::from("Mock Staked CSPR"));
         self.symbol.set(String::from("stCSPR"));
         self.decimals.set(9);
-        self.total_supply.set(0);
+        self.total_supply.set(U256::zero());
+        self.owner.set(self.env().caller());
+        self.exchange_rate.set(U256::from(DECIMALS));
     }
 
     /// Faucet - mint 10,000 stCSPR for testing
     pub fn faucet(&mut self) {
         let caller = self.env().caller();
-        let balance = self.balances.get(&caller).unwrap_or(0);
+        let balance = self.balances.get(&caller).unwrap_or_default();
         self.balances.set(&caller, balance + FAUCET_AMOUNT);
         let supply = self.total_supply.get_or_default();
         self.total_supply.set(supply + FAUCET_AMOUNT);
     }
 
+    /// CSPR backing one stCSPR, 9 decimals
+    pub fn exchange_rate(&self) -> U256 {
+        self.exchange_rate.get_or_default()
+    }
+
+    pub fn set_exchange_rate(&mut self, rate: U256) {
+        self.only_owner();
+        if rate.is_zero() {
+            self.env().revert(Error::InvalidExchangeRate);
+        }
+        self.exchange_rate.set(rate);
+    }
+
+    /// Simulate `rewards` CSPR of staking rewards spread over all stCSPR
+    pub fn accrue_rewards(&mut self, rewards: U256) {
+        self.only_owner();
+        let supply = self.total_supply.get_or_default();
+        if supply.is_zero() {
+            self.env().revert(Error::NoSupply);
+        }
+        let rate = self.exchange_rate.get_or_default();
+        let increase = mul_div(rewards, U256::from(DECIMALS), supply, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        self.exchange_rate.set(rate + increase);
+    }
+
     pub fn name(&self) -> String {
         self.name.get_or_default()
     }
@@ -43,41 +92,53 @@ impl MockStCSPR {
         self.decimals.get_or_default()
     }
 
-    pub fn total_supply(&self) -> u64 {
+    pub fn total_supply(&self) -> U256 {
         self.total_supply.get_or_default()
     }
 
-    pub fn balance_of(&self, address: Address) -> u64 {
-        self.balances.get(&address).unwrap_or(0)
+    pub fn balance_of(&self, address: Address) -> U256 {
+        self.balances.get(&address).unwrap_or_default()
     }
 
-    pub fn allowance(&self, owner: Address, spender: Address) -> u64 {
-        self.allowances.get(&(owner, spender)).unwrap_or(0)
+    pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        self.allowances.get(&(owner, spender)).unwrap_or_default()
     }
 
-    pub fn transfer(&mut self, to: Address, amount: u64) {
+    pub fn transfer(&mut self, to: Address, amount: U256) {
         let caller = self.env().caller();
-        let balance = self.balances.get(&caller).unwrap_or(0);
-        assert!(balance >= amount, "Insufficient balance");
+        let balance = self.balances.get(&caller).unwrap_or_default();
+        if balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
         self.balances.set(&caller, balance - amount);
-        let to_balance = self.balances.get(&to).unwrap_or(0);
+        let to_balance = self.balances.get(&to).unwrap_or_default();
         self.balances.set(&to, to_balance + amount);
     }
 
-    pub fn approve(&mut self, spender: Address, amount: u64) {
+    pub fn approve(&mut self, spender: Address, amount: U256) {
         let caller = self.env().caller();
         self.allowances.set(&(caller, spender), amount);
     }
 
-    pub fn transfer_from(&mut self, from: Address, to: Address, amount: u64) {
+    pub fn transfer_from(&mut self, from: Address, to: Address, amount: U256) {
         let caller = self.env().caller();
-        let allowance = self.allowances.get(&(from, caller)).unwrap_or(0);
-        assert!(allowance >= amount, "Insufficient allowance");
-        let balance = self.balances.get(&from).unwrap_or(0);
-        assert!(balance >= amount, "Insufficient balance");
+        let allowance = self.allowances.get(&(from, caller)).unwrap_or_default();
+        if allowance < amount {
+            self.env().revert(Error::InsufficientAllowance);
+        }
+        let balance = self.balances.get(&from).unwrap_or_default();
+        if balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
         self.allowances.set(&(from, caller), allowance - amount);
         self.balances.set(&from, balance - amount);
-        let to_balance = self.balances.get(&to).unwrap_or(0);
+        let to_balance = self.balances.get(&to).unwrap_or_default();
         self.balances.set(&to, to_balance + amount);
     }
+
+    fn only_owner(&self) {
+        if self.env().caller() != self.owner.get().unwrap() {
+            self.env().revert(Error::InsufficientRights);
+        }
+    }
 }
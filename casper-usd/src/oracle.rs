@@ -1,19 +1,192 @@
 //! TWAP Price Oracle
 //! 
-//! Manipulation-resistant price feed using time-weighted average prices.
+//! Manipulation-resistant price feed:
+//! - Feeders submit prices in rounds, a round settles at the median once
+//!   `quorum` feeders have submitted
+//! - Settled rounds are exposed `latest_round_data`-style to consumers
+//! - A round that moves more than `max_deviation` trips a circuit breaker:
+//!   the oracle freezes at the last accepted price until the move is
+//!   confirmed by further rounds or outlasts a delay
+//! - Every update records a `(timestamp, cumulative_price)` observation
+//! - Observations live in a ring buffer sized to span the TWAP window, one
+//!   per `OBSERVATION_PERIOD` - later updates in a period replace its observation
+//! - `consult(window)` averages the price over the last `window` seconds
 
+use odra::casper_types::{U256, U512};
 use odra::prelude::*;
 
-const MAX_DEVIATION: u64 = 5; // 5% max deviation
-const MAX_STALENESS: u64 = 3600; // 1 hour
+use crate::math::widen;
 
-#[odra::module]
+const MAX_DEVIATION: u64 = 5; // 5% default max move per round
+const MAX_STALENESS: u64 = 3600; // 1 hour default
+const BREAKER_CONFIRMATIONS: u32 = 3; // Rounds confirming a large move
+const BREAKER_DELAY: u64 = 1800; // Or 30 minutes after it tripped
+const TWAP_WINDOW: u64 = 6 * 3600; // 6 hours
+const OBSERVATION_PERIOD: u64 = 60; // One stored observation per period
+const OBSERVATION_CARDINALITY: u32 = (TWAP_WINDOW / OBSERVATION_PERIOD) as u32 + 2; // Ring buffer size
+const MIN_OBSERVATIONS: u32 = 3; // Updates required inside a consulted window
+const ROUND_TIMEOUT: u64 = 3600; // Open rounds restart after 1 hour
+
+/// Price observation - `price_cumulative` is the running sum of price * seconds
+#[odra::odra_type]
+#[derive(Default)]
+pub struct Observation {
+    pub timestamp: u64,
+    pub price: U256,
+    pub price_cumulative: U256,
+}
+
+/// Settled price round
+#[odra::odra_type]
+#[derive(Default)]
+pub struct RoundData {
+    pub round_id: u64,
+    pub answer: U256,
+    // First submission of the round
+    pub started_at: u64,
+    // When quorum was reached
+    pub updated_at: u64,
+    pub submissions: u32,
+}
+
+/// Price submitted by a feeder to the open round
+#[odra::odra_type]
+pub struct Submission {
+    pub feeder: Address,
+    pub price: U256,
+}
+
+/// Feeder's last submission and how far it landed from the round median
+#[odra::odra_type]
+#[derive(Default)]
+pub struct FeederStatus {
+    pub round_id: u64,
+    pub price: U256,
+    pub submitted_at: u64,
+    // Basis points from the settled median, zero until the round settles
+    pub deviation_bps: u64,
+}
+
+/// Owner-settable oracle parameters
+#[odra::odra_type]
+pub struct OracleConfig {
+    // Max move from the last accepted price, in percent
+    pub max_deviation: u64,
+    pub max_staleness: u64,
+    // Consecutive rounds needed to accept a large move (0 disables the breaker)
+    pub breaker_confirmations: u32,
+    // Or time since the breaker tripped
+    pub breaker_delay: u64,
+}
+
+/// Circuit breaker state - `tripped` freezes the oracle
+#[odra::odra_type]
+#[derive(Default)]
+pub struct CircuitBreaker {
+    pub tripped: bool,
+    pub tripped_at: u64,
+    // Direction of the held move
+    pub price_up: bool,
+    // Rounds since the trip that confirmed the move
+    pub confirmations: u32,
+}
+
+/// Whether consumers can rely on the latest accepted price
+#[odra::odra_type]
+pub enum OracleStatus {
+    Active,
+    Stale,
+    Frozen,
+}
+
+/// Oracle errors (codes 100-199)
+#[odra::odra_error]
+pub enum Error {
+    NotOwner = 100,
+    NotFeeder = 101,
+    InvalidPrice = 102,
+    StalePrice = 104,
+    InvalidWindow = 105,
+    InsufficientHistory = 106,
+    InsufficientObservations = 107,
+    InvalidRound = 108,
+    AlreadySubmitted = 109,
+    InvalidQuorum = 110,
+    InvalidParameter = 111,
+}
+
+/// A feeder submitted a price to the open round
+#[odra::event]
+pub struct PriceSubmitted {
+    pub feeder: Address,
+    pub round_id: u64,
+    pub price: U256,
+}
+
+/// A round settled at the median price and was accepted
+#[odra::event]
+pub struct PriceUpdated {
+    pub round_id: u64,
+    pub price: U256,
+    pub twap: U256,
+    pub timestamp: u64,
+}
+
+/// A round moved too far - the oracle is frozen at `last_price`
+#[odra::event]
+pub struct CircuitBreakerTripped {
+    pub round_id: u64,
+    pub price: U256,
+    pub last_price: U256,
+}
+
+/// The breaker cleared, either confirming the move or reverting to normal
+#[odra::event]
+pub struct CircuitBreakerReleased {
+    pub round_id: u64,
+    pub price: U256,
+}
+
+/// Owner changed the oracle parameters
+#[odra::event]
+pub struct ConfigUpdated {
+    pub max_deviation: u64,
+    pub max_staleness: u64,
+    pub breaker_confirmations: u32,
+    pub breaker_delay: u64,
+}
+
+#[odra::module(
+    events = [
+        PriceSubmitted,
+        PriceUpdated,
+        CircuitBreakerTripped,
+        CircuitBreakerReleased,
+        ConfigUpdated
+    ],
+    errors = Error
+)]
 pub struct PriceOracle {
     owner: Var<Address>,
-    current_price: Var<u64>,
-    twap_price: Var<u64>,
-    last_update: Var<u64>,
     feeders: Mapping<Address, bool>,
+    feeder_count: Var<u32>,
+    feeder_status: Mapping<Address, FeederStatus>,
+    quorum: Var<u32>,
+    config: Var<OracleConfig>,
+    breaker: Var<CircuitBreaker>,
+    
+    // Settled rounds, and the submissions to the open one (`latest_round + 1`)
+    // `accepted_round` lags `latest_round` while the breaker holds a move
+    rounds: Mapping<u64, RoundData>,
+    latest_round: Var<u64>,
+    accepted_round: Var<u64>,
+    open_round_started: Var<u64>,
+    open_submissions: Var<Vec<Submission>>,
+    
+    // Ring buffer of observations, `observation_index` is the newest
+    observations: Mapping<u32, Observation>,
+    observation_index: Var<u32>,
+    observation_count: Var<u32>,
 }
 
 #[odra::module]
@@ -22,72 +195,721 @@ impl PriceOracle {
         let caller = self.env().caller();
         self.owner.set(caller);
         self.feeders.set(&caller, true);
-        self.current_price.set(50_000_000); // $0.05
-        self.twap_price.set(50_000_000);
-        self.last_update.set(self.env().get_block_time());
+        self.feeder_count.set(1);
+        self.quorum.set(1);
+        self.config.set(OracleConfig {
+            max_deviation: MAX_DEVIATION,
+            max_staleness: MAX_STALENESS,
+            breaker_confirmations: BREAKER_CONFIRMATIONS,
+            breaker_delay: BREAKER_DELAY,
+        });
+        
+        // Round 0 is the launch price
+        let price = U256::from(50_000_000); // $0.05
+        let now = self.env().get_block_time_secs();
+        self.rounds.set(&0, RoundData {
+            round_id: 0,
+            answer: price,
+            started_at: now,
+            updated_at: now,
+            submissions: 0,
+        });
+        self.latest_round.set(0);
+        self.accepted_round.set(0);
+        
+        self.observations.set(&0, Observation {
+            timestamp: now,
+            price,
+            price_cumulative: U256::zero(),
+        });
+        self.observation_index.set(0);
+        self.observation_count.set(1);
     }
 
     pub fn add_feeder(&mut self, feeder: Address) {
         self.only_owner();
+        if self.is_feeder(feeder) { return; }
         self.feeders.set(&feeder, true);
+        self.feeder_count.set(self.feeder_count.get_or_default() + 1);
     }
 
     pub fn remove_feeder(&mut self, feeder: Address) {
         self.only_owner();
+        if !self.is_feeder(feeder) { return; }
+        let count = self.feeder_count.get_or_default() - 1;
+        if count < self.quorum.get_or_default() {
+            self.env().revert(Error::InvalidQuorum);
+        }
         self.feeders.set(&feeder, false);
+        self.feeder_count.set(count);
     }
 
-    /// Update price with deviation check
-    pub fn update_price(&mut self, new_price: u64) {
-        let caller = self.env().caller();
-        assert!(self.feeders.get(&caller).unwrap_or(false), "Not authorized");
-        assert!(new_price > 0, "Price must be positive");
+    /// Submissions needed to settle a round
+    pub fn set_quorum(&mut self, quorum: u32) {
+        self.only_owner();
+        if quorum == 0 || quorum > self.feeder_count.get_or_default() {
+            self.env().revert(Error::InvalidQuorum);
+        }
+        self.quorum.set(quorum);
+    }
 
-        let twap = self.twap_price.get_or_default();
+    /// Max move per round before the breaker trips, in percent
+    pub fn set_max_deviation(&mut self, percent: u64) {
+        self.only_owner();
+        if percent == 0 || percent >= 100 {
+            self.env().revert(Error::InvalidParameter);
+        }
+        let mut config = self.get_config();
+        config.max_deviation = percent;
+        self.set_config(config);
+    }
 
-        // Deviation check
-        if twap > 0 {
-            let deviation = if new_price > twap {
-                ((new_price - twap) * 100) / twap
-            } else {
-                ((twap - new_price) * 100) / twap
-            };
-            assert!(deviation <= MAX_DEVIATION, "Deviation too high");
+    /// Age after which the accepted price is stale
+    pub fn set_max_staleness(&mut self, secs: u64) {
+        self.only_owner();
+        if secs == 0 {
+            self.env().revert(Error::InvalidParameter);
+        }
+        let mut config = self.get_config();
+        config.max_staleness = secs;
+        self.set_config(config);
+    }
+
+    /// Rounds or delay after which a held move is accepted
+    pub fn set_circuit_breaker(&mut self, confirmations: u32, delay_secs: u64) {
+        self.only_owner();
+        if delay_secs == 0 {
+            self.env().revert(Error::InvalidParameter);
+        }
+        let mut config = self.get_config();
+        config.breaker_confirmations = confirmations;
+        config.breaker_delay = delay_secs;
+        self.set_config(config);
+    }
+
+    /// Submit a price to the open round (`latest_round + 1`)
+    ///
+    /// The round settles at the median once `quorum` feeders have submitted.
+    pub fn submit_price(&mut self, round_id: u64, price: U256) {
+        let caller = self.env().caller();
+        if !self.is_feeder(caller) {
+            self.env().revert(Error::NotFeeder);
+        }
+        if price.is_zero() {
+            self.env().revert(Error::InvalidPrice);
+        }
+        if round_id != self.get_open_round() {
+            self.env().revert(Error::InvalidRound);
         }
 
-        // Simple TWAP: weighted average of old and new
-        let new_twap = (twap * 9 + new_price) / 10;
+        // Drop an open round that never reached quorum
+        let now = self.env().get_block_time_secs();
+        let mut submissions = self.open_submissions.get_or_default();
+        if submissions.is_empty() || now > self.open_round_started.get_or_default() + ROUND_TIMEOUT {
+            submissions.clear();
+            self.open_round_started.set(now);
+        }
+        if submissions.iter().any(|s| s.feeder == caller) {
+            self.env().revert(Error::AlreadySubmitted);
+        }
+        submissions.push(Submission { feeder: caller, price });
         
-        self.twap_price.set(new_twap);
-        self.current_price.set(new_price);
-        self.last_update.set(self.env().get_block_time());
+        self.feeder_status.set(&caller, FeederStatus {
+            round_id,
+            price,
+            submitted_at: now,
+            deviation_bps: 0,
+        });
+        self.env().emit_event(PriceSubmitted { feeder: caller, round_id, price });
+        
+        if submissions.len() < self.quorum.get_or_default() as usize {
+            self.open_submissions.set(submissions);
+        } else {
+            self.settle_round(round_id, submissions, now);
+        }
+    }
+
+    pub fn get_price(&self) -> U256 {
+        self.check_staleness();
+        self.latest_round_data().answer
     }
 
-    pub fn get_price(&self) -> u64 {
+    /// TWAP over the default 6h window
+    ///
+    /// While the oracle is younger than the window, averages over its whole
+    /// history. Past that the buffer always reaches back over the full window.
+    pub fn get_twap_price(&self) -> U256 {
         self.check_staleness();
-        self.current_price.get_or_default()
+        self.twap_at(self.env().get_block_time_secs())
     }
 
-    pub fn get_twap_price(&self) -> u64 {
+    /// Time-weighted average price over the last `window_secs`
+    ///
+    /// Reverts unless the buffer reaches back over the whole window and the
+    /// window holds at least `MIN_OBSERVATIONS` updates.
+    pub fn consult(&self, window_secs: u64) -> U256 {
         self.check_staleness();
-        self.twap_price.get_or_default()
+        if window_secs == 0 {
+            self.env().revert(Error::InvalidWindow);
+        }
+        
+        let now = self.env().get_block_time_secs();
+        let oldest = self.oldest_observation();
+        if now < oldest.timestamp + window_secs {
+            self.env().revert(Error::InsufficientHistory);
+        }
+        
+        let start = now - window_secs;
+        if self.observations_since(start) < MIN_OBSERVATIONS {
+            self.env().revert(Error::InsufficientObservations);
+        }
+        
+        (self.cumulative_at(now) - self.cumulative_at(start)) / window_secs
+    }
+
+    /// Observation `age` updates back from the newest (0 = newest)
+    pub fn get_observation(&self, age: u32) -> Option<Observation> {
+        if age >= self.observation_count.get_or_default() {
+            return None;
+        }
+        self.observations.get(&self.slot(age))
+    }
+
+    pub fn get_observation_count(&self) -> u32 {
+        self.observation_count.get_or_default()
+    }
+
+    /// Latest accepted round - consumers check `get_status` or `updated_at` for freshness
+    pub fn latest_round_data(&self) -> RoundData {
+        self.rounds.get(&self.accepted_round.get_or_default()).unwrap_or_default()
+    }
+
+    /// Round feeders submit to next
+    pub fn get_open_round(&self) -> u64 {
+        self.latest_round.get_or_default() + 1
+    }
+
+    pub fn get_round_data(&self, round_id: u64) -> Option<RoundData> {
+        self.rounds.get(&round_id)
+    }
+
+    pub fn get_feeder_status(&self, feeder: Address) -> Option<FeederStatus> {
+        self.feeder_status.get(&feeder)
+    }
+
+    pub fn is_feeder(&self, address: Address) -> bool {
+        self.feeders.get(&address).unwrap_or(false)
+    }
+
+    pub fn get_quorum(&self) -> u32 {
+        self.quorum.get_or_default()
+    }
+
+    pub fn get_config(&self) -> OracleConfig {
+        self.config.get().unwrap()
+    }
+
+    pub fn get_circuit_breaker(&self) -> CircuitBreaker {
+        self.breaker.get_or_default()
+    }
+
+    /// Frozen while the breaker holds a large move, otherwise stale or active
+    pub fn get_status(&self) -> OracleStatus {
+        if self.is_frozen() {
+            OracleStatus::Frozen
+        } else if self.is_stale() {
+            OracleStatus::Stale
+        } else {
+            OracleStatus::Active
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.breaker.get_or_default().tripped
     }
 
     pub fn is_stale(&self) -> bool {
-        let last = self.last_update.get_or_default();
-        let now = self.env().get_block_time();
-        now > last + MAX_STALENESS
+        let last = self.get_last_update();
+        let now = self.env().get_block_time_secs();
+        now > last + self.get_config().max_staleness
     }
 
     pub fn get_last_update(&self) -> u64 {
-        self.last_update.get_or_default()
+        self.latest_round_data().updated_at
+    }
+
+    // === ROUNDS ===
+
+    /// Settle a round at the median and record each feeder's deviation from it
+    fn settle_round(&mut self, round_id: u64, submissions: Vec<Submission>, now: u64) {
+        let mut prices: Vec<U256> = submissions.iter().map(|s| s.price).collect();
+        let answer = median(&mut prices);
+        
+        for submission in submissions.iter() {
+            let mut status = self.feeder_status.get(&submission.feeder).unwrap_or_default();
+            status.deviation_bps = deviation(submission.price, answer, 10_000);
+            self.feeder_status.set(&submission.feeder, status);
+        }
+        
+        self.rounds.set(&round_id, RoundData {
+            round_id,
+            answer,
+            started_at: self.open_round_started.get_or_default(),
+            updated_at: now,
+            submissions: submissions.len() as u32,
+        });
+        self.latest_round.set(round_id);
+        self.open_submissions.set(Vec::new());
+        
+        if self.passes_circuit_breaker(round_id, answer, now) {
+            self.accepted_round.set(round_id);
+            self.write_observation(now, answer);
+            
+            self.env().emit_event(PriceUpdated {
+                round_id,
+                price: answer,
+                twap: self.twap_at(now),
+                timestamp: now,
+            });
+        }
+    }
+
+    /// Whether a settled round can be accepted, tripping or clearing the breaker
+    ///
+    /// A move beyond `max_deviation` from the last accepted price is held until
+    /// `breaker_confirmations` more rounds move the same way, or a round arrives
+    /// `breaker_delay` after the trip. A round back within range clears it.
+    fn passes_circuit_breaker(&mut self, round_id: u64, price: U256, now: u64) -> bool {
+        let config = self.get_config();
+        let last_price = self.latest_round_data().answer;
+        let mut breaker = self.breaker.get_or_default();
+        
+        if deviation(price, last_price, 100) <= config.max_deviation {
+            if breaker.tripped {
+                self.breaker.set(CircuitBreaker::default());
+                self.env().emit_event(CircuitBreakerReleased { round_id, price });
+            }
+            return true;
+        }
+        
+        let price_up = price > last_price;
+        if breaker.tripped && breaker.price_up == price_up {
+            breaker.confirmations += 1;
+        } else {
+            breaker = CircuitBreaker { tripped: true, tripped_at: now, price_up, confirmations: 0 };
+            self.env().emit_event(CircuitBreakerTripped { round_id, price, last_price });
+        }
+        
+        if breaker.confirmations >= config.breaker_confirmations
+            || now >= breaker.tripped_at + config.breaker_delay
+        {
+            self.breaker.set(CircuitBreaker::default());
+            self.env().emit_event(CircuitBreakerReleased { round_id, price });
+            return true;
+        }
+        
+        self.breaker.set(breaker);
+        false
+    }
+
+    fn set_config(&mut self, config: OracleConfig) {
+        self.env().emit_event(ConfigUpdated {
+            max_deviation: config.max_deviation,
+            max_staleness: config.max_staleness,
+            breaker_confirmations: config.breaker_confirmations,
+            breaker_delay: config.breaker_delay,
+        });
+        self.config.set(config);
+    }
+
+    // === OBSERVATIONS ===
+
+    /// Append an observation, or replace the newest one if it's from the same
+    /// `OBSERVATION_PERIOD`
+    /// 
+    /// Stored observations then fall in distinct periods, so a full buffer
+    /// spans the TWAP window however often rounds settle. The first one, from
+    /// deployment, is never replaced.
+    fn write_observation(&mut self, now: u64, price: U256) {
+        let price_cumulative = self.cumulative_at(now);
+        let mut index = self.observation_index.get_or_default();
+        let newest = self.observations.get(&index).unwrap_or_default();
+        let count = self.observation_count.get_or_default();
+        
+        if count == 1 || now / OBSERVATION_PERIOD != newest.timestamp / OBSERVATION_PERIOD {
+            index = (index + 1) % OBSERVATION_CARDINALITY;
+            self.observation_index.set(index);
+            if count < OBSERVATION_CARDINALITY {
+                self.observation_count.set(count + 1);
+            }
+        }
+        
+        self.observations.set(&index, Observation { timestamp: now, price, price_cumulative });
+    }
+
+    /// TWAP over `TWAP_WINDOW`, shortened to the oracle's age while it's younger
+    fn twap_at(&self, now: u64) -> U256 {
+        let history = now - self.oldest_observation().timestamp;
+        let window = if history < TWAP_WINDOW { history } else { TWAP_WINDOW };
+        if window == 0 {
+            return self.latest_round_data().answer;
+        }
+        (self.cumulative_at(now) - self.cumulative_at(now - window)) / window
+    }
+
+    /// Cumulative price at `timestamp`, extrapolated from the last observation before it
+    fn cumulative_at(&self, timestamp: u64) -> U256 {
+        // Most lookups are for now, which the newest observation covers
+        let mut obs = self.observations.get(&self.slot(0)).unwrap_or_default();
+        if obs.timestamp > timestamp {
+            let count = self.observation_count.get_or_default();
+            let age = self.search_ages(|o| o.timestamp <= timestamp).min(count - 1);
+            obs = self.observations.get(&self.slot(age)).unwrap_or_default();
+        }
+        let elapsed = timestamp.saturating_sub(obs.timestamp);
+        obs.price_cumulative + obs.price * elapsed
+    }
+
+    /// Number of observations at or after `timestamp`
+    fn observations_since(&self, timestamp: u64) -> u32 {
+        self.search_ages(|o| o.timestamp < timestamp)
+    }
+
+    /// Youngest age whose observation matches `is_older`, or the count if none do
+    /// 
+    /// Timestamps fall with age, so this is a binary search over the ring,
+    /// like Uniswap's `observe`: O(log n) reads rather than a scan.
+    fn search_ages(&self, is_older: impl Fn(&Observation) -> bool) -> u32 {
+        let mut low = 0;
+        let mut high = self.observation_count.get_or_default();
+        while low < high {
+            let mid = (low + high) / 2;
+            let obs = self.observations.get(&self.slot(mid)).unwrap_or_default();
+            if is_older(&obs) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
+    }
+
+    fn oldest_observation(&self) -> Observation {
+        let count = self.observation_count.get_or_default();
+        self.observations.get(&self.slot(count - 1)).unwrap_or_default()
+    }
+
+    /// Buffer slot of the observation `age` updates back from the newest
+    fn slot(&self, age: u32) -> u32 {
+        let index = self.observation_index.get_or_default();
+        (index + OBSERVATION_CARDINALITY - age) % OBSERVATION_CARDINALITY
     }
 
     fn check_staleness(&self) {
-        assert!(!self.is_stale(), "Price is stale");
+        if self.is_stale() {
+            self.env().revert(Error::StalePrice);
+        }
     }
 
     fn only_owner(&self) {
-        assert!(self.env().caller() == self.owner.get().unwrap(), "Only owner");
+        if self.env().caller() != self.owner.get().unwrap() {
+            self.env().revert(Error::NotOwner);
+        }
+    }
+}
+
+/// Distance of `price` from `reference`, in `scale` units (100 = percent), rounded up
+///
+/// Saturates at `u64::MAX` - an outlier can be any distance off.
+fn deviation(price: U256, reference: U256, scale: u64) -> u64 {
+    let diff = if price > reference { price - reference } else { reference - price };
+    let reference = widen(reference);
+    let value = (widen(diff) * U512::from(scale) + reference - U512::one()) / reference;
+    if value > U512::from(u64::MAX) { u64::MAX } else { value.as_u64() }
+}
+
+/// Median of a non-empty list, averaging the middle pair for even lengths
+fn median(prices: &mut [U256]) -> U256 {
+    prices.sort();
+    let mid = prices.len() / 2;
+    if prices.len() % 2 == 0 {
+        // Halved separately so outliers can't overflow the sum
+        let (low, high) = (prices[mid - 1], prices[mid]);
+        low / 2 + high / 2 + (low % 2 + high % 2) / 2
+    } else {
+        prices[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, HostEnv, NoArgs};
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    fn setup() -> (HostEnv, PriceOracleHostRef) {
+        let env = odra_test::env();
+        let oracle = PriceOracle::deploy(&env, NoArgs);
+        (env, oracle)
+    }
+
+    /// Submit to the open round as the current caller
+    fn submit(oracle: &mut PriceOracleHostRef, price: U256) {
+        let round = oracle.get_open_round();
+        oracle.submit_price(round, price);
+    }
+
+    #[test]
+    fn consult_weights_prices_by_time() {
+        let (env, mut oracle) = setup();
+        // $0.05 for 30 minutes, then $0.052 for 10 minutes
+        env.advance_block_time(30 * MINUTE_MS);
+        submit(&mut oracle, U256::from(52_000_000));
+        for _ in 0..2 {
+            env.advance_block_time(5 * MINUTE_MS);
+            submit(&mut oracle, U256::from(52_000_000));
+        }
+        
+        let expected = (50_000_000u64 * 30 + 52_000_000 * 10) / 40;
+        assert_eq!(oracle.consult(40 * 60), U256::from(expected));
+        assert_eq!(oracle.consult(10 * 60), U256::from(52_000_000));
+        assert_eq!(oracle.get_twap_price(), U256::from(expected));
+        assert!(env.emitted_event(&oracle, PriceUpdated {
+            round_id: oracle.latest_round_data().round_id,
+            price: U256::from(52_000_000),
+            twap: U256::from(expected),
+            timestamp: env.block_time_secs(),
+        }));
+    }
+
+    #[test]
+    fn consult_requires_history_and_observations() {
+        let (env, mut oracle) = setup();
+        env.advance_block_time(30 * MINUTE_MS);
+        submit(&mut oracle, U256::from(51_000_000));
+        
+        assert_eq!(oracle.try_consult(0), Err(Error::InvalidWindow.into()));
+        assert_eq!(oracle.try_consult(31 * 60), Err(Error::InsufficientHistory.into()));
+        // Only the initial price and one update in the window
+        assert_eq!(oracle.try_consult(30 * 60), Err(Error::InsufficientObservations.into()));
+    }
+
+    #[test]
+    fn round_settles_at_median_once_quorum_is_reached() {
+        let (env, mut oracle) = setup();
+        let feeders = [env.get_account(0), env.get_account(1), env.get_account(2)];
+        oracle.add_feeder(feeders[1]);
+        oracle.add_feeder(feeders[2]);
+        oracle.set_quorum(3);
+        env.advance_block_time(MINUTE_MS);
+        
+        let prices = [51_000_000u64, 49_000_000, 50_500_000];
+        for (feeder, price) in feeders.iter().zip(prices) {
+            assert_eq!(oracle.latest_round_data().round_id, 0);
+            env.set_caller(*feeder);
+            oracle.submit_price(1, U256::from(price));
+        }
+        
+        let round = oracle.latest_round_data();
+        assert_eq!(round.round_id, 1);
+        assert_eq!(round.answer, U256::from(50_500_000));
+        assert_eq!(round.submissions, 3);
+        assert_eq!(oracle.get_price(), U256::from(50_500_000));
+        
+        // 49.0 vs 50.5 is ~2.97%, rounded up to the next basis point
+        let status = oracle.get_feeder_status(feeders[1]).unwrap();
+        assert_eq!(status.round_id, 1);
+        assert_eq!(status.deviation_bps, 298);
+        assert_eq!(oracle.get_feeder_status(feeders[2]).unwrap().deviation_bps, 0);
+    }
+
+    #[test]
+    fn outlier_submission_cannot_stall_settlement() {
+        let (env, mut oracle) = setup();
+        let feeders = [env.get_account(0), env.get_account(1), env.get_account(2)];
+        oracle.add_feeder(feeders[1]);
+        oracle.add_feeder(feeders[2]);
+        oracle.set_quorum(3);
+        env.advance_block_time(MINUTE_MS);
+        
+        // Trillions of times the median - far past u64::MAX basis points
+        let outlier = U256::from(10u64).pow(U256::from(30));
+        let prices = [U256::from(50_000_000), outlier, U256::from(50_500_000)];
+        for (feeder, price) in feeders.iter().zip(prices) {
+            env.set_caller(*feeder);
+            oracle.submit_price(1, price);
+        }
+        
+        // The median discards the outlier, its feeder's deviation saturates
+        assert_eq!(oracle.get_price(), U256::from(50_500_000));
+        assert_eq!(oracle.get_feeder_status(feeders[1]).unwrap().deviation_bps, u64::MAX);
+        
+        // A round settled at the outlier trips the breaker instead
+        env.set_caller(feeders[0]);
+        oracle.set_quorum(1);
+        submit(&mut oracle, outlier);
+        assert!(oracle.is_frozen());
+        assert_eq!(oracle.latest_round_data().answer, U256::from(50_500_000));
+    }
+
+    #[test]
+    fn submissions_are_checked_against_the_open_round() {
+        let (env, mut oracle) = setup();
+        oracle.add_feeder(env.get_account(1));
+        oracle.set_quorum(2);
+        let price = U256::from(50_000_000);
+        
+        assert_eq!(oracle.try_submit_price(0, price), Err(Error::InvalidRound.into()));
+        assert_eq!(oracle.try_submit_price(2, price), Err(Error::InvalidRound.into()));
+        oracle.submit_price(1, price);
+        assert_eq!(oracle.try_submit_price(1, price), Err(Error::AlreadySubmitted.into()));
+        assert_eq!(oracle.try_remove_feeder(env.get_account(1)), Err(Error::InvalidQuorum.into()));
+        
+        // A round that sat open past the timeout starts over
+        env.advance_block_time((ROUND_TIMEOUT + 1) * 1000);
+        oracle.submit_price(1, price);
+        env.set_caller(env.get_account(1));
+        assert_eq!(oracle.latest_round_data().round_id, 0);
+        oracle.submit_price(1, price);
+        
+        let round = oracle.latest_round_data();
+        assert_eq!(round.round_id, 1);
+        assert_eq!(round.started_at, round.updated_at);
+    }
+
+    #[test]
+    fn large_move_freezes_until_confirmed() {
+        let (env, mut oracle) = setup();
+        let crash = U256::from(35_000_000);
+        
+        // -30% trips the breaker: the round settles but isn't accepted
+        submit(&mut oracle, crash);
+        assert!(oracle.is_frozen());
+        assert!(matches!(oracle.get_status(), OracleStatus::Frozen));
+        assert_eq!(oracle.get_price(), U256::from(50_000_000));
+        assert_eq!(oracle.get_round_data(1).unwrap().answer, crash);
+        assert_eq!(oracle.latest_round_data().round_id, 0);
+        
+        // Accepted on the third confirming round
+        for _ in 0..BREAKER_CONFIRMATIONS {
+            assert!(oracle.is_frozen());
+            env.advance_block_time(60 * 1000);
+            submit(&mut oracle, crash);
+        }
+        assert!(matches!(oracle.get_status(), OracleStatus::Active));
+        assert_eq!(oracle.get_price(), crash);
+        assert_eq!(oracle.latest_round_data().round_id, 4);
+    }
+
+    #[test]
+    fn breaker_releases_on_delay_or_return_to_range() {
+        let (env, mut oracle) = setup();
+        
+        // A spike followed by a normal round is discarded
+        submit(&mut oracle, U256::from(60_000_000));
+        assert!(oracle.is_frozen());
+        submit(&mut oracle, U256::from(51_000_000));
+        assert!(!oracle.is_frozen());
+        assert_eq!(oracle.get_price(), U256::from(51_000_000));
+        
+        // A held move is accepted by the first round after the delay
+        oracle.set_circuit_breaker(10, 600);
+        submit(&mut oracle, U256::from(40_000_000));
+        env.advance_block_time(600 * 1000);
+        submit(&mut oracle, U256::from(40_000_000));
+        assert!(!oracle.is_frozen());
+        assert_eq!(oracle.get_price(), U256::from(40_000_000));
+    }
+
+    #[test]
+    fn owner_sets_deviation_and_staleness() {
+        let (env, mut oracle) = setup();
+        oracle.set_max_deviation(50);
+        oracle.set_max_staleness(60);
+        assert_eq!(oracle.try_set_max_deviation(0), Err(Error::InvalidParameter.into()));
+        assert_eq!(oracle.try_set_max_staleness(0), Err(Error::InvalidParameter.into()));
+        
+        submit(&mut oracle, U256::from(70_000_000));
+        assert!(!oracle.is_frozen());
+        
+        env.advance_block_time(61 * 1000);
+        assert!(matches!(oracle.get_status(), OracleStatus::Stale));
+        assert_eq!(oracle.try_get_price(), Err(Error::StalePrice.into()));
+        
+        env.set_caller(env.get_account(1));
+        assert_eq!(oracle.try_set_max_deviation(10), Err(Error::NotOwner.into()));
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_latest_observations() {
+        let (env, mut oracle) = setup();
+        for _ in 0..OBSERVATION_CARDINALITY + 10 {
+            env.advance_block_time(MINUTE_MS);
+            submit(&mut oracle, U256::from(50_000_000));
+        }
+        // Same-second updates replace the newest observation
+        submit(&mut oracle, U256::from(51_000_000));
+        
+        assert_eq!(oracle.get_observation_count(), OBSERVATION_CARDINALITY);
+        let newest = oracle.get_observation(0).unwrap();
+        let oldest = oracle.get_observation(OBSERVATION_CARDINALITY - 1).unwrap();
+        assert_eq!(newest.price, U256::from(51_000_000));
+        assert_eq!(newest.timestamp - oldest.timestamp, (OBSERVATION_CARDINALITY as u64 - 1) * 60);
+        assert_eq!(oracle.get_observation(OBSERVATION_CARDINALITY), None);
+        
+        // The overwritten history can no longer be consulted
+        let window = OBSERVATION_CARDINALITY as u64 * 60;
+        assert_eq!(oracle.try_consult(window), Err(Error::InsufficientHistory.into()));
+        assert_eq!(oracle.consult(window - 60), U256::from(50_000_000));
+        
+        // So do later ones in the same period
+        env.advance_block_time(OBSERVATION_PERIOD * 1000 - 1000);
+        submit(&mut oracle, U256::from(51_000_000));
+        assert_eq!(oracle.get_observation(0).unwrap().timestamp, newest.timestamp + 59);
+        assert_eq!(oracle.get_observation(1).unwrap().timestamp, newest.timestamp - 60);
+    }
+
+    #[test]
+    fn lookups_find_the_observation_anywhere_in_a_wrapped_ring() {
+        let (env, mut oracle) = setup();
+        let minutes = OBSERVATION_CARDINALITY as usize + 10;
+        let prices: Vec<u64> = (0..minutes).map(|i| 50_000_000 + (i as u64 % 7) * 100_000).collect();
+        for price in &prices {
+            env.advance_block_time(MINUTE_MS);
+            submit(&mut oracle, U256::from(*price));
+        }
+        env.advance_block_time(MINUTE_MS);
+        
+        // Each price held for a minute, so a window of `n` minutes averages the last `n`
+        for n in [3, 7, 50, 181, OBSERVATION_CARDINALITY as usize - 2] {
+            let expected = prices[minutes - n..].iter().sum::<u64>() / n as u64;
+            assert_eq!(oracle.consult(n as u64 * 60), U256::from(expected));
+        }
+    }
+
+    #[test]
+    fn twap_spans_the_window_under_frequent_updates() {
+        let (env, mut oracle) = setup();
+        let tick = 10;
+        // 6h at $0.051, then 3h at $0.052, settling every 10 seconds
+        for _ in 0..TWAP_WINDOW / tick {
+            env.advance_block_time(tick * 1000);
+            submit(&mut oracle, U256::from(51_000_000));
+        }
+        for _ in 0..TWAP_WINDOW / tick / 2 {
+            env.advance_block_time(tick * 1000);
+            submit(&mut oracle, U256::from(52_000_000));
+        }
+        
+        assert_eq!(oracle.get_observation_count(), OBSERVATION_CARDINALITY);
+        let newest = oracle.get_observation(0).unwrap();
+        let oldest = oracle.get_observation(OBSERVATION_CARDINALITY - 1).unwrap();
+        assert!(newest.timestamp - oldest.timestamp >= TWAP_WINDOW);
+        
+        // $0.052 since the first round after the switch, one tick into the second half
+        let half = TWAP_WINDOW / 2;
+        let expected = (51_000_000 * (half + tick) + 52_000_000 * (half - tick)) / TWAP_WINDOW;
+        assert_eq!(oracle.get_twap_price(), U256::from(expected));
     }
 }
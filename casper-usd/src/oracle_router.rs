@@ -0,0 +1,491 @@
+//! Oracle Router - Primary/Secondary Price Feed
+//!
+//! Liquity's PriceFeed status machine over two `PriceOracle` sources:
+//! - The primary is used while it's live and agrees with the secondary
+//! - Falls back to the secondary when the primary is stale, frozen by its
+//!   circuit breaker, or diverges beyond `max_divergence`
+//! - Returns to the primary once both are live and agree again
+//! - With neither source trusted, serves the last good price
+//!
+//! Both sources quote CSPR/USD. Prices handed to consumers are per stCSPR:
+//! the CSPR price times the LST's exchange rate, so collateral value grows
+//! with staking rewards.
+
+use odra::casper_types::U256;
+use odra::prelude::*;
+use odra::ContractRef;
+
+use crate::math::{dec_mul, mul_div, Rounding};
+use crate::oracle::{OracleStatus, PriceOracleContractRef};
+use crate::token::LiquidStakingTokenContractRef;
+
+const MAX_DIVERGENCE: u64 = 5; // 5% default max primary/secondary gap
+
+/// Which sources the router currently trusts
+#[odra::odra_type]
+pub enum PriceFeedStatus {
+    PrimaryWorking,
+    UsingSecondaryPrimaryUntrusted,
+    BothUntrusted,
+    UsingSecondaryPrimaryFrozen,
+    UsingPrimarySecondaryUntrusted,
+}
+
+/// Where the last fetched price came from
+#[odra::odra_type]
+pub enum PriceSource {
+    Primary,
+    Secondary,
+    LastGood,
+}
+
+/// Oracle router errors (codes 600-699)
+#[odra::odra_error]
+pub enum Error {
+    NotOwner = 600,
+    InvalidParameter = 601,
+    PrimaryNotLive = 602,
+    InvalidExchangeRate = 603,
+    MathOverflow = 604,
+}
+
+/// The router moved to a new status
+#[odra::event]
+pub struct PriceFeedStatusChanged {
+    pub status: PriceFeedStatus,
+}
+
+/// A fresh CSPR price was stored as the last good price
+#[odra::event]
+pub struct LastGoodPriceUpdated {
+    pub price: U256,
+    pub source: PriceSource,
+}
+
+#[odra::module(events = [PriceFeedStatusChanged, LastGoodPriceUpdated], errors = Error)]
+pub struct OracleRouter {
+    owner: Var<Address>,
+    primary: Var<Address>,
+    secondary: Var<Address>,
+    // stCSPR contract, for the stCSPR -> CSPR exchange rate
+    lst: Var<Address>,
+    max_divergence: Var<u64>,
+    status: Var<PriceFeedStatus>,
+    active_source: Var<PriceSource>,
+    last_good_price: Var<U256>,
+}
+
+/// Health of one source, as Liquity sees it
+enum Health {
+    Live(U256),
+    // Timed out - Liquity's "frozen"
+    Stale,
+    // Circuit breaker tripped - Liquity's "broken"
+    Broken,
+}
+
+/// What one step of the status machine decides
+struct Step {
+    // None keeps the current status
+    status: Option<PriceFeedStatus>,
+    quote: Quote,
+}
+
+/// The CSPR price a step serves
+enum Quote {
+    Primary(U256),
+    Secondary(U256),
+    LastGood,
+}
+
+impl Step {
+    fn stay(quote: Quote) -> Self {
+        Step { status: None, quote }
+    }
+
+    fn to(status: PriceFeedStatus, quote: Quote) -> Self {
+        Step { status: Some(status), quote }
+    }
+}
+
+#[odra::module]
+impl OracleRouter {
+    /// Both sources are CSPR/USD `PriceOracle` contracts; the primary must be live
+    pub fn init(&mut self, primary: Address, secondary: Address, lst: Address) {
+        self.owner.set(self.env().caller());
+        self.primary.set(primary);
+        self.secondary.set(secondary);
+        self.lst.set(lst);
+        self.max_divergence.set(MAX_DIVERGENCE);
+        self.status.set(PriceFeedStatus::PrimaryWorking);
+
+        match self.read(primary) {
+            Health::Live(price) => self.store(price, PriceSource::Primary),
+            _ => self.env().revert(Error::PrimaryNotLive),
+        };
+    }
+
+    /// Max gap between the sources before the primary is distrusted, in percent
+    pub fn set_max_divergence(&mut self, percent: u64) {
+        self.only_owner();
+        if percent == 0 || percent >= 100 {
+            self.env().revert(Error::InvalidParameter);
+        }
+        self.max_divergence.set(percent);
+    }
+
+    /// Read both sources, step the status machine and return the stCSPR price to use
+    pub fn fetch_price(&mut self) -> U256 {
+        let price = self.fetch_cspr_price();
+        self.to_collateral_price(price)
+    }
+
+    /// stCSPR price the next fetch would return, without storing it or moving
+    /// the status - for views. `None` when it would serve the last good price.
+    pub fn peek_price(&self) -> Option<U256> {
+        match self.next_step().quote {
+            Quote::Primary(price) | Quote::Secondary(price) => Some(self.to_collateral_price(price)),
+            Quote::LastGood => None,
+        }
+    }
+
+    /// TWAP of the source the last fetch used, or the last good price, per stCSPR
+    pub fn get_twap_price(&self) -> U256 {
+        let twap = match self.get_active_source() {
+            PriceSource::Primary => self.oracle_ref(self.primary.get().unwrap()).get_twap_price(),
+            PriceSource::Secondary => self.oracle_ref(self.secondary.get().unwrap()).get_twap_price(),
+            PriceSource::LastGood => self.last_good_price.get_or_default(),
+        };
+        self.to_collateral_price(twap)
+    }
+
+    // === VIEW FUNCTIONS ===
+
+    pub fn get_status(&self) -> PriceFeedStatus {
+        self.status.get().unwrap()
+    }
+
+    pub fn get_active_source(&self) -> PriceSource {
+        self.active_source.get().unwrap()
+    }
+
+    /// Last good CSPR/USD price
+    pub fn get_last_good_price(&self) -> U256 {
+        self.last_good_price.get_or_default()
+    }
+
+    /// CSPR per stCSPR, 9 decimals
+    pub fn get_exchange_rate(&self) -> U256 {
+        let rate = LiquidStakingTokenContractRef::new(self.env(), self.lst.get().unwrap()).exchange_rate();
+        if rate.is_zero() {
+            self.env().revert(Error::InvalidExchangeRate);
+        }
+        rate
+    }
+
+    pub fn get_primary(&self) -> Address {
+        self.primary.get().unwrap()
+    }
+
+    pub fn get_secondary(&self) -> Address {
+        self.secondary.get().unwrap()
+    }
+
+    pub fn get_max_divergence(&self) -> u64 {
+        self.max_divergence.get_or_default()
+    }
+
+    // === INTERNAL ===
+
+    /// Apply one step of the status machine and return the CSPR price to use
+    fn fetch_cspr_price(&mut self) -> U256 {
+        let step = self.next_step();
+        if let Some(status) = step.status {
+            self.change_status(status);
+        }
+        match step.quote {
+            Quote::Primary(price) => self.store(price, PriceSource::Primary),
+            Quote::Secondary(price) => self.store(price, PriceSource::Secondary),
+            Quote::LastGood => self.last_good(),
+        }
+    }
+
+    /// Liquity's status machine over the two CSPR/USD sources
+    fn next_step(&self) -> Step {
+        let primary = self.read(self.primary.get().unwrap());
+        let secondary = self.read(self.secondary.get().unwrap());
+        let agree = self.agree(&primary, &secondary);
+
+        match self.get_status() {
+            PriceFeedStatus::PrimaryWorking => match (primary, secondary) {
+                (Health::Broken, Health::Broken) => {
+                    Step::to(PriceFeedStatus::BothUntrusted, Quote::LastGood)
+                }
+                (Health::Broken, Health::Stale) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::LastGood)
+                }
+                (Health::Broken, Health::Live(s)) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::Secondary(s))
+                }
+                (Health::Stale, Health::Broken) => {
+                    Step::to(PriceFeedStatus::UsingPrimarySecondaryUntrusted, Quote::LastGood)
+                }
+                (Health::Stale, Health::Stale) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryFrozen, Quote::LastGood)
+                }
+                (Health::Stale, Health::Live(s)) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryFrozen, Quote::Secondary(s))
+                }
+                (Health::Live(p), Health::Broken) => {
+                    Step::to(PriceFeedStatus::UsingPrimarySecondaryUntrusted, Quote::Primary(p))
+                }
+                (Health::Live(p), Health::Stale) => Step::stay(Quote::Primary(p)),
+                (Health::Live(p), Health::Live(s)) => {
+                    if agree {
+                        Step::stay(Quote::Primary(p))
+                    } else {
+                        Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::Secondary(s))
+                    }
+                }
+            },
+
+            PriceFeedStatus::UsingSecondaryPrimaryUntrusted => match (primary, secondary) {
+                (Health::Live(p), _) if agree => {
+                    Step::to(PriceFeedStatus::PrimaryWorking, Quote::Primary(p))
+                }
+                (_, Health::Broken) => Step::to(PriceFeedStatus::BothUntrusted, Quote::LastGood),
+                (_, Health::Stale) => Step::stay(Quote::LastGood),
+                (_, Health::Live(s)) => Step::stay(Quote::Secondary(s)),
+            },
+
+            PriceFeedStatus::BothUntrusted => match primary {
+                Health::Live(p) if agree => {
+                    Step::to(PriceFeedStatus::PrimaryWorking, Quote::Primary(p))
+                }
+                _ => Step::stay(Quote::LastGood),
+            },
+
+            PriceFeedStatus::UsingSecondaryPrimaryFrozen => match (primary, secondary) {
+                (Health::Broken, Health::Broken) => {
+                    Step::to(PriceFeedStatus::BothUntrusted, Quote::LastGood)
+                }
+                (Health::Broken, Health::Stale) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::LastGood)
+                }
+                (Health::Broken, Health::Live(s)) => {
+                    Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::Secondary(s))
+                }
+                (Health::Stale, Health::Broken) => {
+                    Step::to(PriceFeedStatus::UsingPrimarySecondaryUntrusted, Quote::LastGood)
+                }
+                (Health::Stale, Health::Stale) => Step::stay(Quote::LastGood),
+                (Health::Stale, Health::Live(s)) => Step::stay(Quote::Secondary(s)),
+                (Health::Live(p), Health::Broken | Health::Stale) => {
+                    Step::to(PriceFeedStatus::UsingPrimarySecondaryUntrusted, Quote::Primary(p))
+                }
+                (Health::Live(p), Health::Live(s)) => {
+                    if agree {
+                        Step::to(PriceFeedStatus::PrimaryWorking, Quote::Primary(p))
+                    } else {
+                        Step::to(PriceFeedStatus::UsingSecondaryPrimaryUntrusted, Quote::Secondary(s))
+                    }
+                }
+            },
+
+            PriceFeedStatus::UsingPrimarySecondaryUntrusted => match primary {
+                Health::Broken => Step::to(PriceFeedStatus::BothUntrusted, Quote::LastGood),
+                Health::Stale => Step::stay(Quote::LastGood),
+                Health::Live(p) if agree => {
+                    Step::to(PriceFeedStatus::PrimaryWorking, Quote::Primary(p))
+                }
+                Health::Live(p) => Step::stay(Quote::Primary(p)),
+            },
+        }
+    }
+
+    /// stCSPR price from a CSPR price, rounded down in the protocol's favour
+    fn to_collateral_price(&self, cspr_price: U256) -> U256 {
+        dec_mul(cspr_price, self.get_exchange_rate(), Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    fn read(&self, oracle: Address) -> Health {
+        let oracle = self.oracle_ref(oracle);
+        match oracle.get_status() {
+            OracleStatus::Active => Health::Live(oracle.latest_round_data().answer),
+            OracleStatus::Stale => Health::Stale,
+            OracleStatus::Frozen => Health::Broken,
+        }
+    }
+
+    /// Both sources live and within `max_divergence` of each other
+    fn agree(&self, primary: &Health, secondary: &Health) -> bool {
+        let (Health::Live(p), Health::Live(s)) = (primary, secondary) else {
+            return false;
+        };
+        let (low, high) = if p < s { (*p, *s) } else { (*s, *p) };
+        let divergence = mul_div(high - low, U256::from(100), low, Rounding::Up)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        divergence <= U256::from(self.max_divergence.get_or_default())
+    }
+
+    fn store(&mut self, price: U256, source: PriceSource) -> U256 {
+        self.last_good_price.set(price);
+        self.active_source.set(source.clone());
+        self.env().emit_event(LastGoodPriceUpdated { price, source });
+        price
+    }
+
+    fn last_good(&mut self) -> U256 {
+        self.active_source.set(PriceSource::LastGood);
+        self.last_good_price.get_or_default()
+    }
+
+    fn change_status(&mut self, status: PriceFeedStatus) {
+        self.status.set(status.clone());
+        self.env().emit_event(PriceFeedStatusChanged { status });
+    }
+
+    fn oracle_ref(&self, oracle: Address) -> PriceOracleContractRef {
+        PriceOracleContractRef::new(self.env(), oracle)
+    }
+
+    fn only_owner(&self) {
+        if self.env().caller() != self.owner.get().unwrap() {
+            self.env().revert(Error::NotOwner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_stcspr::{MockStCSPR, MockStCSPRHostRef};
+    use crate::oracle::{PriceOracle, PriceOracleHostRef};
+    use odra::host::{Deployer, HostEnv, NoArgs};
+    use odra::prelude::Addressable;
+
+    struct Feeds {
+        env: HostEnv,
+        primary: PriceOracleHostRef,
+        secondary: PriceOracleHostRef,
+        router: OracleRouterHostRef,
+        stcspr: MockStCSPRHostRef,
+    }
+
+    fn setup() -> Feeds {
+        let env = odra_test::env();
+        let primary = PriceOracle::deploy(&env, NoArgs);
+        let secondary = PriceOracle::deploy(&env, NoArgs);
+        let stcspr = MockStCSPR::deploy(&env, NoArgs);
+        let router = OracleRouter::deploy(&env, OracleRouterInitArgs {
+            primary: primary.address(),
+            secondary: secondary.address(),
+            lst: stcspr.address(),
+        });
+        Feeds { env, primary, secondary, router, stcspr }
+    }
+
+    fn feed(oracle: &mut PriceOracleHostRef, price: u64) {
+        let round = oracle.get_open_round();
+        oracle.submit_price(round, U256::from(price));
+    }
+
+    #[test]
+    fn falls_back_when_the_primary_diverges() {
+        let mut f = setup();
+        feed(&mut f.primary, 52_500_000);
+        
+        // 5% apart still agrees
+        assert_eq!(f.router.fetch_price(), U256::from(52_500_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::PrimaryWorking));
+        
+        feed(&mut f.secondary, 48_000_000);
+        assert_eq!(f.router.fetch_price(), U256::from(48_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::UsingSecondaryPrimaryUntrusted));
+        assert!(matches!(f.router.get_active_source(), PriceSource::Secondary));
+        
+        // Back to the primary once they agree again
+        feed(&mut f.primary, 50_000_000);
+        assert_eq!(f.router.fetch_price(), U256::from(50_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::PrimaryWorking));
+    }
+
+    #[test]
+    fn peeking_leaves_the_status_alone() {
+        let mut f = setup();
+        feed(&mut f.primary, 52_500_000);
+        feed(&mut f.secondary, 48_000_000);
+        let events = f.env.events_count(&f.router);
+        
+        // Shows what a fetch would serve, without switching to the secondary
+        assert_eq!(f.router.peek_price(), Some(U256::from(48_000_000)));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::PrimaryWorking));
+        assert!(matches!(f.router.get_active_source(), PriceSource::Primary));
+        assert_eq!(f.env.events_count(&f.router), events);
+        
+        assert_eq!(f.router.fetch_price(), U256::from(48_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::UsingSecondaryPrimaryUntrusted));
+        
+        // Nothing to peek at once both breakers trip
+        feed(&mut f.primary, 30_000_000);
+        feed(&mut f.secondary, 30_000_000);
+        assert_eq!(f.router.peek_price(), None);
+    }
+
+    #[test]
+    fn uses_the_secondary_while_the_primary_is_stale() {
+        let mut f = setup();
+        f.env.advance_block_time(3_601 * 1000);
+        feed(&mut f.secondary, 51_000_000);
+        
+        assert_eq!(f.router.fetch_price(), U256::from(51_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::UsingSecondaryPrimaryFrozen));
+        
+        // Primary back and in agreement
+        feed(&mut f.primary, 51_000_000);
+        assert_eq!(f.router.fetch_price(), U256::from(51_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::PrimaryWorking));
+        assert!(matches!(f.router.get_active_source(), PriceSource::Primary));
+    }
+
+    #[test]
+    fn serves_the_last_good_price_when_both_are_untrusted() {
+        let mut f = setup();
+        feed(&mut f.primary, 30_000_000);
+        feed(&mut f.secondary, 30_000_000);
+        
+        assert_eq!(f.router.fetch_price(), U256::from(50_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::BothUntrusted));
+        assert!(matches!(f.router.get_active_source(), PriceSource::LastGood));
+        assert_eq!(f.router.get_twap_price(), U256::from(50_000_000));
+        
+        // Both breakers accept the move after three confirmations
+        for _ in 0..3 {
+            f.env.advance_block_time(60 * 1000);
+            feed(&mut f.primary, 30_000_000);
+            feed(&mut f.secondary, 30_000_000);
+        }
+        assert_eq!(f.router.fetch_price(), U256::from(30_000_000));
+        assert!(matches!(f.router.get_status(), PriceFeedStatus::PrimaryWorking));
+    }
+
+    #[test]
+    fn prices_stcspr_at_the_exchange_rate() {
+        let mut f = setup();
+        f.stcspr.faucet();
+        // 10,000 stCSPR earn 500 CSPR: 1.05 CSPR each
+        f.stcspr.accrue_rewards(U256::from(500_000_000_000u64));
+        assert_eq!(f.router.get_exchange_rate(), U256::from(1_050_000_000));
+        
+        assert_eq!(f.router.fetch_price(), U256::from(52_500_000));
+        assert_eq!(f.router.get_twap_price(), U256::from(52_500_000));
+        assert_eq!(f.router.get_last_good_price(), U256::from(50_000_000));
+        
+        f.env.set_caller(f.env.get_account(1));
+        assert_eq!(
+            f.stcspr.try_set_exchange_rate(U256::from(2_000_000_000)),
+            Err(crate::mock_stcspr::Error::InsufficientRights.into())
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Redistribution Rewards
+//!
+//! Liquity's fallback for liquidations the Stability Pool can't absorb:
+//! - Uncovered debt and collateral are spread over all active troves
+//! - Pro-rata by stake, via cumulative per-stake accumulators (L_coll, L_debt)
+//! - Each trove keeps a snapshot and applies its share lazily when touched
+//! - Accumulators carry 18 decimals, and the remainder each division drops
+//!   is carried into the next redistribution, so nothing leaks over time
+//! - Stakes are scaled by the stake/collateral snapshot of the last
+//!   liquidation, so rewards already handed out aren't earned twice
+
+use odra::casper_types::U256;
+use odra::prelude::*;
+
+use crate::math::{mul_div, Rounding};
+
+const DECIMAL_PRECISION: u64 = 1_000_000_000_000_000_000; // L_coll / L_debt precision
+
+/// Redistribution errors (codes 400-449)
+#[odra::odra_error]
+pub enum Error {
+    NoStakes = 400,
+    MathOverflow = 401,
+}
+
+#[odra::module(errors = Error)]
+pub struct Redistribution {
+    // Cumulative collateral / debt redistributed per unit of stake
+    l_coll: Var<U256>,
+    l_debt: Var<U256>,
+    // Remainders of the last per-stake division, times DECIMAL_PRECISION
+    last_coll_error: Var<U256>,
+    last_debt_error: Var<U256>,
+    // Sum of all active trove stakes
+    total_stakes: Var<U256>,
+    // Total stakes and collateral right after the last liquidation
+    total_stakes_snapshot: Var<U256>,
+    total_collateral_snapshot: Var<U256>,
+}
+
+#[odra::module]
+impl Redistribution {
+    /// Spread collateral and debt over all current stakes
+    pub fn redistribute(&mut self, collateral: U256, debt: U256) {
+        let total_stakes = self.total_stakes.get_or_default();
+        if total_stakes.is_zero() {
+            self.env().revert(Error::NoStakes);
+        }
+
+        // Rounded down so troves are never assigned more than was redistributed,
+        // with the remainder held back for the next call - Liquity's
+        // lastETHError_Redistribution
+        let (coll_per_stake, coll_error) =
+            self.per_stake(collateral, self.last_coll_error.get_or_default(), total_stakes);
+        let (debt_per_stake, debt_error) =
+            self.per_stake(debt, self.last_debt_error.get_or_default(), total_stakes);
+        self.last_coll_error.set(coll_error);
+        self.last_debt_error.set(debt_error);
+        
+        self.l_coll.set(self.l_coll.get_or_default() + coll_per_stake);
+        self.l_debt.set(self.l_debt.get_or_default() + debt_per_stake);
+    }
+
+    /// Replace a trove's old stake with its new one in the total
+    pub fn update_stake(&mut self, old_stake: U256, new_stake: U256) {
+        let total = self.total_stakes.get_or_default();
+        self.total_stakes.set(total - old_stake + new_stake);
+    }
+
+    /// Record the stakes and collateral left after a liquidation
+    /// 
+    /// `total_collateral` includes redistributed collateral not yet applied
+    /// to the troves - Liquity's totalCollateralSnapshot.
+    pub fn update_snapshots(&mut self, total_collateral: U256) {
+        self.total_stakes_snapshot.set(self.total_stakes.get_or_default());
+        self.total_collateral_snapshot.set(total_collateral);
+    }
+
+    /// Stake for a trove holding `collateral`
+    /// 
+    /// Until the first liquidation this is the collateral itself. After it,
+    /// `collateral * totalStakesSnapshot / totalCollateralSnapshot`: older
+    /// stakes don't count their pending rewards, so a fresh trove's stake
+    /// is scaled down to match.
+    pub fn compute_stake(&self, collateral: U256) -> U256 {
+        let total_stakes = self.total_stakes_snapshot.get_or_default();
+        let total_collateral = self.total_collateral_snapshot.get_or_default();
+        if total_stakes.is_zero() || total_collateral.is_zero() {
+            return collateral;
+        }
+        mul_div(collateral, total_stakes, total_collateral, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    /// Rewards earned by a stake since the given snapshots: (collateral, debt)
+    pub fn pending_rewards(&self, stake: U256, l_coll_snapshot: U256, l_debt_snapshot: U256) -> (U256, U256) {
+        let coll_delta = self.l_coll.get_or_default() - l_coll_snapshot;
+        let debt_delta = self.l_debt.get_or_default() - l_debt_snapshot;
+        let coll = mul_div(stake, coll_delta, DECIMAL_PRECISION.into(), Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let debt = mul_div(stake, debt_delta, DECIMAL_PRECISION.into(), Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        (coll, debt)
+    }
+
+    pub fn l_coll(&self) -> U256 {
+        self.l_coll.get_or_default()
+    }
+
+    pub fn l_debt(&self) -> U256 {
+        self.l_debt.get_or_default()
+    }
+
+    pub fn total_stakes(&self) -> U256 {
+        self.total_stakes.get_or_default()
+    }
+
+    /// `amount` per unit of stake plus the carried `error`, and the new remainder
+    fn per_stake(&self, amount: U256, error: U256, total_stakes: U256) -> (U256, U256) {
+        let numerator = amount
+            .checked_mul(DECIMAL_PRECISION.into())
+            .and_then(|scaled| scaled.checked_add(error))
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let per_stake = numerator / total_stakes;
+        (per_stake, numerator - per_stake * total_stakes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, NoArgs};
+
+    #[test]
+    fn division_remainders_carry_into_the_next_redistribution() {
+        let env = odra_test::env();
+        let mut rewards = Redistribution::deploy(&env, NoArgs);
+        rewards.update_stake(U256::zero(), U256::from(3));
+        
+        // A third each time - dropped, the remainders would lose a unit
+        for _ in 0..3 {
+            rewards.redistribute(U256::one(), U256::one());
+        }
+        assert_eq!(rewards.l_coll(), U256::from(DECIMAL_PRECISION));
+        let pending = rewards.pending_rewards(U256::from(3), U256::zero(), U256::zero());
+        assert_eq!(pending, (U256::from(3), U256::from(3)));
+    }
+
+    #[test]
+    fn stakes_follow_the_last_snapshot() {
+        let env = odra_test::env();
+        let mut rewards = Redistribution::deploy(&env, NoArgs);
+        rewards.update_stake(U256::zero(), U256::from(300));
+        assert_eq!(rewards.compute_stake(U256::from(50)), U256::from(50));
+
+        // 300 of stake now stands for 400 of collateral
+        rewards.redistribute(U256::from(100), U256::from(10));
+        rewards.update_snapshots(U256::from(400));
+        assert_eq!(rewards.compute_stake(U256::from(400)), U256::from(300));
+        assert_eq!(rewards.compute_stake(U256::from(50)), U256::from(37));
+    }
+}
@@ -0,0 +1,317 @@
+//! Sorted Troves
+//!
+//! Doubly-linked list of trove owners ordered by interest rate (ascending).
+//! The head is the lowest-rate trove - first in line for redemptions.
+//!
+//! Based on Liquity's SortedTroves:
+//! - Callers pass `prev`/`next` hints for the insert position
+//! - Valid hints make insertion O(1), stale ones fall back to a walk from the hint
+
+use odra::prelude::*;
+
+/// Sorted list errors (codes 450-459)
+#[odra::odra_error]
+pub enum Error {
+    NodeAlreadyInList = 450,
+    NodeNotInList = 451,
+}
+
+#[odra::module(errors = Error)]
+pub struct SortedTroves {
+    head: Var<Option<Address>>,
+    tail: Var<Option<Address>>,
+    size: Var<u64>,
+    // Node storage
+    next: Mapping<Address, Option<Address>>,
+    prev: Mapping<Address, Option<Address>>,
+    keys: Mapping<Address, u64>,
+    contains: Mapping<Address, bool>,
+}
+
+#[odra::module]
+impl SortedTroves {
+    /// Insert a node, after any existing nodes with the same key
+    pub fn insert(
+        &mut self,
+        id: Address,
+        key: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>,
+    ) {
+        if self.contains(id) {
+            self.env().revert(Error::NodeAlreadyInList);
+        }
+
+        let (prev, next) = self.find_insert_position(key, prev_hint, next_hint);
+        self.link(id, key, prev, next);
+    }
+
+    /// Move a node to the position for its new key
+    pub fn re_insert(
+        &mut self,
+        id: Address,
+        new_key: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>,
+    ) {
+        self.remove(id);
+        self.insert(id, new_key, prev_hint, next_hint);
+    }
+
+    pub fn remove(&mut self, id: Address) {
+        if !self.contains(id) {
+            self.env().revert(Error::NodeNotInList);
+        }
+
+        let prev = self.prev.get(&id).flatten();
+        let next = self.next.get(&id).flatten();
+
+        match prev {
+            Some(p) => self.next.set(&p, next),
+            None => self.head.set(next),
+        }
+        match next {
+            Some(n) => self.prev.set(&n, prev),
+            None => self.tail.set(prev),
+        }
+
+        self.next.set(&id, None);
+        self.prev.set(&id, None);
+        self.keys.set(&id, 0);
+        self.contains.set(&id, false);
+        self.size.set(self.size.get_or_default() - 1);
+    }
+
+    pub fn contains(&self, id: Address) -> bool {
+        self.contains.get(&id).unwrap_or(false)
+    }
+
+    pub fn first(&self) -> Option<Address> {
+        self.head.get_or_default()
+    }
+
+    pub fn last(&self) -> Option<Address> {
+        self.tail.get_or_default()
+    }
+
+    pub fn next(&self, id: Address) -> Option<Address> {
+        self.next.get(&id).flatten()
+    }
+
+    pub fn prev(&self, id: Address) -> Option<Address> {
+        self.prev.get(&id).flatten()
+    }
+
+    pub fn key(&self, id: Address) -> u64 {
+        self.keys.get(&id).unwrap_or(0)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size.get_or_default()
+    }
+
+    /// Up to `count` nodes, starting at `start` (or the head) and moving
+    /// towards the tail
+    pub fn page(&self, start: Option<Address>, count: u32) -> Vec<Address> {
+        let mut result = Vec::new();
+        let mut current = match start {
+            Some(id) => {
+                if !self.contains(id) {
+                    self.env().revert(Error::NodeNotInList);
+                }
+                Some(id)
+            }
+            None => self.first(),
+        };
+        while let Some(id) = current {
+            if result.len() as u32 >= count {
+                break;
+            }
+            result.push(id);
+            current = self.next(id);
+        }
+        result
+    }
+
+    /// Whether `key` fits between `prev` and `next` with no node in between
+    pub fn is_valid_insert_position(
+        &self,
+        key: u64,
+        prev: Option<Address>,
+        next: Option<Address>,
+    ) -> bool {
+        match (prev, next) {
+            (None, None) => self.size() == 0,
+            (None, Some(n)) => self.first() == Some(n) && key < self.key(n),
+            (Some(p), None) => self.last() == Some(p) && self.key(p) <= key,
+            (Some(p), Some(n)) => {
+                self.next(p) == Some(n) && self.key(p) <= key && key < self.key(n)
+            }
+        }
+    }
+
+    /// Resolve hints into an exact `(prev, next)` position for `key`
+    pub fn find_insert_position(
+        &self,
+        key: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>,
+    ) -> (Option<Address>, Option<Address>) {
+        if self.is_valid_insert_position(key, prev_hint, next_hint) {
+            return (prev_hint, next_hint);
+        }
+
+        // Hints that left the list or sit on the wrong side of `key` are dropped
+        let prev_hint = prev_hint.filter(|p| self.contains(*p) && self.key(*p) <= key);
+        let next_hint = next_hint.filter(|n| self.contains(*n) && key < self.key(*n));
+
+        match (prev_hint, next_hint) {
+            (Some(p), _) => self.descend_from(key, Some(p)),
+            (None, Some(n)) => self.ascend_from(key, n),
+            (None, None) => self.descend_from(key, None),
+        }
+    }
+
+    /// Walk towards the tail from `start` (or the head)
+    fn descend_from(&self, key: u64, start: Option<Address>) -> (Option<Address>, Option<Address>) {
+        let mut prev = start;
+        let mut next = match start {
+            Some(p) => self.next(p),
+            None => self.first(),
+        };
+        while let Some(node) = next {
+            if self.key(node) > key {
+                break;
+            }
+            prev = Some(node);
+            next = self.next(node);
+        }
+        (prev, next)
+    }
+
+    /// Walk towards the head from `start`
+    fn ascend_from(&self, key: u64, start: Address) -> (Option<Address>, Option<Address>) {
+        let mut next = start;
+        let mut prev = self.prev(start);
+        while let Some(node) = prev {
+            if self.key(node) <= key {
+                break;
+            }
+            next = node;
+            prev = self.prev(node);
+        }
+        (prev, Some(next))
+    }
+
+    fn link(&mut self, id: Address, key: u64, prev: Option<Address>, next: Option<Address>) {
+        self.prev.set(&id, prev);
+        self.next.set(&id, next);
+        match prev {
+            Some(p) => self.next.set(&p, Some(id)),
+            None => self.head.set(Some(id)),
+        }
+        match next {
+            Some(n) => self.prev.set(&n, Some(id)),
+            None => self.tail.set(Some(id)),
+        }
+
+        self.keys.set(&id, key);
+        self.contains.set(&id, true);
+        self.size.set(self.size.get_or_default() + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, HostEnv, NoArgs};
+
+    /// A list holding accounts 1..=3 at keys 10, 20 and 30
+    fn setup() -> (HostEnv, SortedTrovesHostRef, [Address; 3]) {
+        let env = odra_test::env();
+        let mut list = SortedTroves::deploy(&env, NoArgs);
+        let ids = [env.get_account(1), env.get_account(2), env.get_account(3)];
+        for (id, key) in ids.iter().zip([10, 20, 30]) {
+            list.insert(*id, key, None, None);
+        }
+        (env, list, ids)
+    }
+
+    fn keys(list: &SortedTrovesHostRef) -> Vec<u64> {
+        list.page(None, 10).into_iter().map(|id| list.key(id)).collect()
+    }
+
+    #[test]
+    fn valid_hints_are_taken_as_they_are() {
+        let (_, list, [a, b, c]) = setup();
+        assert_eq!(list.find_insert_position(15, Some(a), Some(b)), (Some(a), Some(b)));
+        assert_eq!(list.find_insert_position(5, None, Some(a)), (None, Some(a)));
+        assert_eq!(list.find_insert_position(35, Some(c), None), (Some(c), None));
+        // Equal keys go after the existing node
+        assert_eq!(list.find_insert_position(20, Some(b), Some(c)), (Some(b), Some(c)));
+        assert!(!list.is_valid_insert_position(20, Some(a), Some(b)));
+    }
+
+    #[test]
+    fn stale_hints_walk_to_the_right_position() {
+        let (_, list, [a, b, c]) = setup();
+        // A prev hint below the key descends towards the tail
+        assert_eq!(list.find_insert_position(25, Some(a), Some(b)), (Some(b), Some(c)));
+        assert_eq!(list.find_insert_position(35, Some(a), None), (Some(c), None));
+        // A next hint above the key ascends towards the head
+        assert_eq!(list.find_insert_position(15, None, Some(c)), (Some(a), Some(b)));
+        assert_eq!(list.find_insert_position(5, None, Some(c)), (None, Some(a)));
+        // Hints on the wrong side of the key are dropped, leaving a walk from the head
+        assert_eq!(list.find_insert_position(15, Some(c), Some(a)), (Some(a), Some(b)));
+    }
+
+    #[test]
+    fn removed_hints_are_ignored() {
+        let (env, mut list, [a, b, c]) = setup();
+        list.remove(b);
+        let stranger = env.get_account(4);
+        assert_eq!(list.find_insert_position(25, Some(b), None), (Some(a), Some(c)));
+        assert_eq!(list.find_insert_position(25, None, Some(b)), (Some(a), Some(c)));
+        assert_eq!(list.find_insert_position(25, Some(stranger), Some(b)), (Some(a), Some(c)));
+    }
+
+    #[test]
+    fn re_insert_moves_a_node() {
+        let (_, mut list, [a, b, c]) = setup();
+        list.re_insert(a, 40, Some(c), None);
+        assert_eq!(list.page(None, 10), vec![b, c, a]);
+        assert_eq!((list.first(), list.last()), (Some(b), Some(a)));
+        assert_eq!(list.prev(a), Some(c));
+
+        // Back to the head through stale hints
+        list.re_insert(a, 1, Some(c), Some(b));
+        assert_eq!(keys(&list), vec![1, 20, 30]);
+        assert_eq!(list.size(), 3);
+    }
+
+    #[test]
+    fn pages_walk_towards_the_tail() {
+        let (env, mut list, [a, b, c]) = setup();
+        assert_eq!(list.page(None, 2), vec![a, b]);
+        assert_eq!(list.page(Some(b), 10), vec![b, c]);
+        assert!(list.page(Some(c), 0).is_empty());
+
+        list.remove(b);
+        assert_eq!(list.page(None, 10), vec![a, c]);
+        assert_eq!(list.try_page(Some(b), 1), Err(Error::NodeNotInList.into()));
+        assert_eq!(list.try_page(Some(env.get_account(4)), 1), Err(Error::NodeNotInList.into()));
+    }
+
+    #[test]
+    fn membership_is_checked() {
+        let (env, mut list, [a, b, _]) = setup();
+        assert_eq!(list.try_insert(a, 50, None, None), Err(Error::NodeAlreadyInList.into()));
+        list.remove(b);
+        assert_eq!(list.try_remove(b), Err(Error::NodeNotInList.into()));
+        assert_eq!(list.try_re_insert(b, 5, None, None), Err(Error::NodeNotInList.into()));
+        assert!(!list.contains(b));
+        assert!(list.contains(a));
+        assert!(!list.contains(env.get_account(4)));
+        assert_eq!(list.size(), 2);
+    }
+}
@@ -5,127 +5,255 @@
 //! - Earn liquidation gains (collateral at discount)
 //! - Earn share of protocol interest revenue
 //! - Primary liquidation mechanism (more efficient than auctions)
+//! - Liquity's product-sum accounting: a running product P compounds
+//!   deposit losses, per-epoch/scale sums S (collateral) and B (interest)
+//!   track gains, so every depositor is settled exactly in O(1)
+//! - Gains are also bucketed by day, with a running sum for a trailing APY
 
+use odra::casper_types::U256;
 use odra::prelude::*;
+use odra::ContractRef;
 
-const DECIMALS: u64 = 1_000_000_000;
-const SCALE_FACTOR: u64 = 1_000_000_000_000_000_000; // For precision
+use crate::gain_window::{GainWindow, APY_WINDOW_DAYS};
+use crate::math::{dec_mul, mul_div, Rounding, DECIMALS};
+use crate::stablecoin::CasperUSDContractRef;
+use crate::token::Cep18TokenContractRef;
+use crate::trove_manager::TroveManagerContractRef;
 
-#[odra::module]
+const DECIMAL_PRECISION: u64 = 1_000_000_000_000_000_000; // P and per-unit values
+const SCALE_FACTOR: u64 = 1_000_000_000; // P is rescaled by this before it loses precision
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Stability Pool errors (codes 500-599)
+#[odra::odra_error]
+pub enum Error {
+    ZeroAmount = 500,
+    InsufficientDeposit = 501,
+    NotTroveManager = 502,
+    OffsetExceedsDeposits = 503,
+    NoDeposits = 504,
+    MathOverflow = 505,
+}
+
+/// Accumulator values a deposit was last settled at
+#[odra::odra_type]
+#[derive(Default)]
+pub struct DepositSnapshot {
+    pub p: U256,
+    pub s: U256,
+    pub b: U256,
+    pub scale: u64,
+    pub epoch: u64,
+}
+
+/// cUSD deposited into the pool
+#[odra::event]
+pub struct StabilityDeposit {
+    pub depositor: Address,
+    pub amount: U256,
+    pub deposit: U256,
+}
+
+/// cUSD withdrawn from the pool
+#[odra::event]
+pub struct StabilityWithdrawal {
+    pub depositor: Address,
+    pub amount: U256,
+    pub deposit: U256,
+}
+
+/// Collateral gain paid out, along with the deposit loss it came from
+#[odra::event]
+pub struct CollateralGainClaimed {
+    pub depositor: Address,
+    pub collateral: U256,
+    pub deposit_loss: U256,
+}
+
+/// Liquidated debt cancelled against the pool
+#[odra::event]
+pub struct LiquidationOffset {
+    pub debt: U256,
+    pub collateral: U256,
+    // Trailing APY after this offset, 9 decimals
+    pub apy: u64,
+}
+
+/// Interest revenue received from the TroveManager
+#[odra::event]
+pub struct InterestReceived {
+    pub amount: U256,
+    // Trailing APY after this payment, 9 decimals
+    pub apy: u64,
+}
+
+/// Interest gain paid out to a depositor
+#[odra::event]
+pub struct InterestGainClaimed {
+    pub depositor: Address,
+    pub amount: U256,
+}
+
+#[odra::module(
+    events = [
+        StabilityDeposit,
+        StabilityWithdrawal,
+        CollateralGainClaimed,
+        LiquidationOffset,
+        InterestReceived,
+        InterestGainClaimed
+    ],
+    errors = Error
+)]
 pub struct StabilityPool {
     owner: Var<Address>,
     stablecoin: Var<Address>,
+    stcspr_token: Var<Address>,
     trove_manager: Var<Address>,
     
-    // Deposits
-    deposits: Mapping<Address, u64>,
-    total_deposits: Var<u64>,
+    // Deposits as of their last settlement, and the accumulators at that point
+    deposits: Mapping<Address, U256>,
+    deposit_snapshots: Mapping<Address, DepositSnapshot>,
+    total_deposits: Var<U256>,
     
-    // Reward tracking (for proportional distribution)
-    // Using Liquity's "product" algorithm for O(1) reward calculation
-    cumulative_collateral_per_unit: Var<u64>,
-    cumulative_cusd_loss_per_unit: Var<u64>,
+    // Running product of (1 - loss per unit) over all offsets - compounds losses
+    p: Var<U256>,
+    // Bumped each time P is rescaled by SCALE_FACTOR
+    current_scale: Var<u64>,
+    // Bumped each time an offset empties the pool, P restarts at 1
+    current_epoch: Var<u64>,
     
-    // User snapshots for reward calculation
-    user_collateral_snapshot: Mapping<Address, u64>,
-    user_loss_snapshot: Mapping<Address, u64>,
+    // Collateral (S) and interest (B) gain sums, per epoch and scale
+    epoch_to_scale_to_sum: Mapping<(u64, u64), U256>,
+    epoch_to_scale_to_b: Mapping<(u64, u64), U256>,
     
     // Collateral gains from liquidations
-    collateral_balance: Var<u64>,
+    collateral_balance: Var<U256>,
+    
+    // Interest revenue not yet claimed - held as cUSD alongside deposits
+    interest_balance: Var<U256>,
     
-    // Interest revenue distribution
-    pending_interest_revenue: Var<u64>,
+    // cUSD value gained per unit deposited (18 decimals), by day
+    gains: SubModule<GainWindow>,
 }
 
 #[odra::module]
 impl StabilityPool {
-    pub fn init(&mut self, stablecoin: Address, trove_manager: Address) {
+    pub fn init(&mut self, stablecoin: Address, stcspr: Address, trove_manager: Address) {
         self.owner.set(self.env().caller());
         self.stablecoin.set(stablecoin);
+        self.stcspr_token.set(stcspr);
         self.trove_manager.set(trove_manager);
-        self.total_deposits.set(0);
-        self.collateral_balance.set(0);
-        self.cumulative_collateral_per_unit.set(0);
-        self.cumulative_cusd_loss_per_unit.set(0);
-        self.pending_interest_revenue.set(0);
+        self.total_deposits.set(U256::zero());
+        self.collateral_balance.set(U256::zero());
+        self.interest_balance.set(U256::zero());
+        self.p.set(U256::from(DECIMAL_PRECISION));
+        self.current_scale.set(0);
+        self.current_epoch.set(0);
     }
 
     // === DEPOSIT/WITHDRAW ===
 
     /// Deposit cUSD to earn liquidation gains + interest
-    pub fn deposit(&mut self, amount: u64) {
+    pub fn deposit(&mut self, amount: U256) {
         let caller = self.env().caller();
-        assert!(amount > 0, "Amount must be positive");
+        if amount.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
         
-        // Claim any pending rewards first
-        self.claim_rewards_internal(caller);
+        // Interest earned so far belongs to the deposits already here
+        self.mint_pending_interest();
         
-        // Update deposit
-        let current = self.deposits.get(&caller).unwrap_or(0);
-        self.deposits.set(&caller, current + amount);
+        // Pay out gains and apply liquidation losses first
+        let current = self.settle_deposit(caller);
+        self.set_deposit(caller, current + amount);
         
         // Update total
         let total = self.total_deposits.get_or_default();
         self.total_deposits.set(total + amount);
         
-        // Take snapshot for future reward calculation
-        self.update_user_snapshot(caller);
+        // Pull cUSD from user (requires prior approval)
+        let this = self.env().self_address();
+        self.cusd_ref().transfer_from(caller, this, amount);
         
-        // TODO: Transfer cUSD from user
+        self.env().emit_event(StabilityDeposit {
+            depositor: caller,
+            amount,
+            deposit: current + amount,
+        });
     }
 
     /// Withdraw cUSD deposit
-    pub fn withdraw(&mut self, amount: u64) {
+    pub fn withdraw(&mut self, amount: U256) {
         let caller = self.env().caller();
-        let current = self.deposits.get(&caller).unwrap_or(0);
-        assert!(current >= amount, "Insufficient deposit");
-        
-        // Claim rewards first
-        self.claim_rewards_internal(caller);
+        self.mint_pending_interest();
         
-        // Update deposit
-        self.deposits.set(&caller, current - amount);
+        // Pay out gains first - this also applies liquidation losses
+        let current = self.settle_deposit(caller);
+        if current < amount {
+            self.env().revert(Error::InsufficientDeposit);
+        }
+        self.set_deposit(caller, current - amount);
         
         // Update total
         let total = self.total_deposits.get_or_default();
         self.total_deposits.set(total - amount);
         
-        // Update snapshot
-        self.update_user_snapshot(caller);
+        self.cusd_ref().transfer(caller, amount);
         
-        // TODO: Transfer cUSD to user
+        self.env().emit_event(StabilityWithdrawal {
+            depositor: caller,
+            amount,
+            deposit: current - amount,
+        });
     }
 
-    /// Claim accumulated rewards (collateral gains)
+    /// Claim accumulated rewards (collateral and interest gains)
     pub fn claim_rewards(&mut self) {
         let caller = self.env().caller();
-        self.claim_rewards_internal(caller);
+        self.mint_pending_interest();
+        let current = self.settle_deposit(caller);
+        self.set_deposit(caller, current);
     }
 
     // === LIQUIDATION INTERFACE ===
 
     /// Called by TroveManager during liquidation
-    /// Absorbs debt and receives collateral
-    pub fn offset(&mut self, debt_to_offset: u64, collateral_to_add: u64) {
+    /// Absorbs debt and receives collateral, worth `price` cUSD per unit
+    /// 
+    /// Gains round down and losses up, so the pool never owes more than it
+    /// holds. An offset that uses up every deposit starts a new epoch.
+    pub fn offset(&mut self, debt_to_offset: U256, collateral_to_add: U256, price: U256) {
         // Only TroveManager can call
-        assert!(
-            self.env().caller() == self.trove_manager.get().unwrap(),
-            "Only TroveManager"
-        );
+        if self.env().caller() != self.trove_manager.get().unwrap() {
+            self.env().revert(Error::NotTroveManager);
+        }
         
         let total = self.total_deposits.get_or_default();
-        if total == 0 { return; }
+        if debt_to_offset > total {
+            self.env().revert(Error::OffsetExceedsDeposits);
+        }
+        if debt_to_offset.is_zero() { return; }
         
-        // Calculate per-unit gains/losses
-        let collateral_per_unit = (collateral_to_add * SCALE_FACTOR) / total;
-        let loss_per_unit = (debt_to_offset * SCALE_FACTOR) / total;
+        let precision = U256::from(DECIMAL_PRECISION);
+        let collateral_per_unit = mul_div(collateral_to_add, precision, total, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let loss_per_unit = if debt_to_offset == total {
+            precision
+        } else {
+            // A partial offset must leave P above zero
+            let loss = mul_div(debt_to_offset, precision, total, Rounding::Up)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            if loss < precision { loss } else { precision - 1 }
+        };
         
-        // Update cumulative values
-        let cum_coll = self.cumulative_collateral_per_unit.get_or_default();
-        self.cumulative_collateral_per_unit.set(cum_coll + collateral_per_unit);
+        // Collateral gain is credited at the P deposits had before this loss
+        let p = self.p.get_or_default();
+        let key = (self.current_epoch.get_or_default(), self.current_scale.get_or_default());
+        let sum = self.epoch_to_scale_to_sum.get(&key).unwrap_or_default();
+        self.epoch_to_scale_to_sum.set(&key, sum + collateral_per_unit * p);
         
-        let cum_loss = self.cumulative_cusd_loss_per_unit.get_or_default();
-        self.cumulative_cusd_loss_per_unit.set(cum_loss + loss_per_unit);
+        self.update_product(p, precision - loss_per_unit);
         
         // Update balances
         let coll_bal = self.collateral_balance.get_or_default();
@@ -133,93 +261,457 @@ impl StabilityPool {
         
         // Reduce total deposits by absorbed debt
         self.total_deposits.set(total - debt_to_offset);
+        
+        // Depositors gain the liquidation discount
+        let value = dec_mul(collateral_to_add, price, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        self.record_gain(value.saturating_sub(debt_to_offset), total);
+        
+        self.env().emit_event(LiquidationOffset {
+            debt: debt_to_offset,
+            collateral: collateral_to_add,
+            apy: self.get_effective_apy(),
+        });
     }
 
-    /// Receive interest revenue for distribution
-    pub fn receive_interest(&mut self, amount: u64) {
+    /// Receive interest revenue, already minted to the pool, for distribution
+    /// 
+    /// Spread over current deposits the same way as collateral gains.
+    pub fn receive_interest(&mut self, amount: U256) {
         // Only TroveManager can call
-        assert!(
-            self.env().caller() == self.trove_manager.get().unwrap(),
-            "Only TroveManager"
-        );
+        if self.env().caller() != self.trove_manager.get().unwrap() {
+            self.env().revert(Error::NotTroveManager);
+        }
         
-        let pending = self.pending_interest_revenue.get_or_default();
-        self.pending_interest_revenue.set(pending + amount);
+        let total = self.total_deposits.get_or_default();
+        if total.is_zero() {
+            self.env().revert(Error::NoDeposits);
+        }
+        if amount.is_zero() { return; }
+        
+        let interest_per_unit = mul_div(amount, DECIMAL_PRECISION.into(), total, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let key = (self.current_epoch.get_or_default(), self.current_scale.get_or_default());
+        let b = self.epoch_to_scale_to_b.get(&key).unwrap_or_default();
+        self.epoch_to_scale_to_b.set(&key, b + interest_per_unit * self.p.get_or_default());
+        
+        let balance = self.interest_balance.get_or_default();
+        self.interest_balance.set(balance + amount);
+        self.record_gain(amount, total);
+        
+        self.env().emit_event(InterestReceived { amount, apy: self.get_effective_apy() });
     }
 
     // === INTERNAL ===
 
-    fn claim_rewards_internal(&mut self, user: Address) {
-        let deposit = self.deposits.get(&user).unwrap_or(0);
-        if deposit == 0 { return; }
-        
-        // Calculate collateral gain since last snapshot
-        let cum_coll = self.cumulative_collateral_per_unit.get_or_default();
-        let user_coll_snap = self.user_collateral_snapshot.get(&user).unwrap_or(0);
-        let coll_gain = (deposit * (cum_coll - user_coll_snap)) / SCALE_FACTOR;
-        
-        // Calculate deposit loss from liquidations
-        let cum_loss = self.cumulative_cusd_loss_per_unit.get_or_default();
-        let user_loss_snap = self.user_loss_snapshot.get(&user).unwrap_or(0);
-        let deposit_loss = (deposit * (cum_loss - user_loss_snap)) / SCALE_FACTOR;
+    /// Multiply P by `factor` (1 - loss per unit), rescaling or starting
+    /// a new epoch before it can lose precision
+    fn update_product(&mut self, p: U256, factor: U256) {
+        let precision = U256::from(DECIMAL_PRECISION);
+        if factor.is_zero() {
+            self.current_epoch.set(self.current_epoch.get_or_default() + 1);
+            self.current_scale.set(0);
+            self.p.set(precision);
+            return;
+        }
         
-        // Update user's deposit (reduced by absorbed debt)
-        if deposit_loss > 0 {
-            let new_deposit = if deposit > deposit_loss { deposit - deposit_loss } else { 0 };
-            self.deposits.set(&user, new_deposit);
+        let new_p = mul_div(p, factor, precision, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        if new_p < U256::from(SCALE_FACTOR) {
+            self.current_scale.set(self.current_scale.get_or_default() + 1);
+            let rescaled = mul_div(p, factor * SCALE_FACTOR, precision, Rounding::Down)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            self.p.set(rescaled);
+        } else {
+            self.p.set(new_p);
         }
+    }
+
+    /// Add a gain of `value` cUSD over `total` deposits to today's bucket
+    fn record_gain(&mut self, value: U256, total: U256) {
+        if value.is_zero() { return; }
+        let day = self.env().get_block_time_secs() / SECONDS_PER_DAY;
+        let per_unit = mul_div(value, DECIMAL_PRECISION.into(), total, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        self.gains.record(day, per_unit);
+    }
+
+    /// Pay out a depositor's gains and return their compounded deposit
+    /// 
+    /// The caller stores the new deposit with `set_deposit`, which also
+    /// takes the fresh snapshot.
+    fn settle_deposit(&mut self, user: Address) -> U256 {
+        let deposit = self.deposits.get(&user).unwrap_or_default();
+        if deposit.is_zero() { return U256::zero(); }
+        
+        let coll_gain = self.get_pending_collateral_gain(user);
+        let interest_gain = self.get_pending_interest_gain(user);
+        let compounded = self.get_deposit(user);
+        let deposit_loss = deposit - compounded;
         
         // Transfer collateral gain to user
-        if coll_gain > 0 {
+        if !coll_gain.is_zero() {
             let coll_bal = self.collateral_balance.get_or_default();
             self.collateral_balance.set(coll_bal - coll_gain);
-            // TODO: Transfer collateral to user
+            self.stcspr_ref().transfer(user, coll_gain);
         }
         
-        self.update_user_snapshot(user);
+        // Transfer interest gain to user
+        if !interest_gain.is_zero() {
+            let balance = self.interest_balance.get_or_default();
+            self.interest_balance.set(balance - interest_gain);
+            self.cusd_ref().transfer(user, interest_gain);
+            self.env().emit_event(InterestGainClaimed { depositor: user, amount: interest_gain });
+        }
+        
+        if !coll_gain.is_zero() || !deposit_loss.is_zero() {
+            self.env().emit_event(CollateralGainClaimed {
+                depositor: user,
+                collateral: coll_gain,
+                deposit_loss,
+            });
+        }
+        compounded
     }
 
-    fn update_user_snapshot(&mut self, user: Address) {
-        self.user_collateral_snapshot.set(
-            &user, 
-            self.cumulative_collateral_per_unit.get_or_default()
-        );
-        self.user_loss_snapshot.set(
-            &user,
-            self.cumulative_cusd_loss_per_unit.get_or_default()
-        );
+    /// Store a deposit along with the current accumulators
+    fn set_deposit(&mut self, user: Address, amount: U256) {
+        self.deposits.set(&user, amount);
+        if amount.is_zero() {
+            self.deposit_snapshots.set(&user, DepositSnapshot::default());
+            return;
+        }
+        
+        let epoch = self.current_epoch.get_or_default();
+        let scale = self.current_scale.get_or_default();
+        self.deposit_snapshots.set(&user, DepositSnapshot {
+            p: self.p.get_or_default(),
+            s: self.epoch_to_scale_to_sum.get(&(epoch, scale)).unwrap_or_default(),
+            b: self.epoch_to_scale_to_b.get(&(epoch, scale)).unwrap_or_default(),
+            scale,
+            epoch,
+        });
+    }
+
+    /// Gain of a deposit from a sum (S or B) since its snapshot
+    /// 
+    /// Covers the snapshot's scale and the next one - anything later
+    /// is too small to register.
+    fn gain_from_sums(
+        &self,
+        user: Address,
+        sums: &Mapping<(u64, u64), U256>,
+        snapshot_sum: impl Fn(&DepositSnapshot) -> U256
+    ) -> U256 {
+        let deposit = self.deposits.get(&user).unwrap_or_default();
+        if deposit.is_zero() { return U256::zero(); }
+        
+        let snapshot = self.deposit_snapshots.get(&user).unwrap_or_default();
+        let first = sums.get(&(snapshot.epoch, snapshot.scale)).unwrap_or_default() - snapshot_sum(&snapshot);
+        let second = sums.get(&(snapshot.epoch, snapshot.scale + 1)).unwrap_or_default() / SCALE_FACTOR;
+        
+        mul_div(deposit, first + second, snapshot.p * DECIMAL_PRECISION, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    /// Have the TroveManager mint the interest accrued since its last update
+    /// 
+    /// As in Liquity V2, this runs before any deposit changes so the yield
+    /// goes to the deposits that earned it. A plain account standing in
+    /// as manager has nothing to mint.
+    fn mint_pending_interest(&mut self) {
+        let manager = self.trove_manager.get().unwrap();
+        if manager.is_contract() {
+            TroveManagerContractRef::new(self.env(), manager).mint_interest();
+        }
+    }
+
+    fn cusd_ref(&self) -> CasperUSDContractRef {
+        CasperUSDContractRef::new(self.env(), self.stablecoin.get().unwrap())
+    }
+
+    fn stcspr_ref(&self) -> Cep18TokenContractRef {
+        Cep18TokenContractRef::new(self.env(), self.stcspr_token.get().unwrap())
     }
 
     // === VIEW FUNCTIONS ===
 
-    pub fn get_deposit(&self, user: Address) -> u64 {
-        self.deposits.get(&user).unwrap_or(0)
+    /// Deposit after liquidation losses since it was last settled
+    pub fn get_deposit(&self, user: Address) -> U256 {
+        let deposit = self.deposits.get(&user).unwrap_or_default();
+        if deposit.is_zero() { return U256::zero(); }
+        
+        // Emptied by an offset since the snapshot
+        let snapshot = self.deposit_snapshots.get(&user).unwrap_or_default();
+        if snapshot.epoch < self.current_epoch.get_or_default() { return U256::zero(); }
+        
+        let p = self.p.get_or_default();
+        let compounded = match self.current_scale.get_or_default() - snapshot.scale {
+            0 => mul_div(deposit, p, snapshot.p, Rounding::Down),
+            1 => mul_div(deposit, p, snapshot.p * SCALE_FACTOR, Rounding::Down),
+            _ => Some(U256::zero()),
+        }
+        .unwrap_or_revert_with(self, Error::MathOverflow);
+        
+        // Below a billionth of the original it's rounding noise
+        if compounded < deposit / SCALE_FACTOR { U256::zero() } else { compounded }
     }
 
-    pub fn get_total_deposits(&self) -> u64 {
+    pub fn get_total_deposits(&self) -> U256 {
         self.total_deposits.get_or_default()
     }
 
-    pub fn get_collateral_balance(&self) -> u64 {
+    pub fn get_collateral_balance(&self) -> U256 {
         self.collateral_balance.get_or_default()
     }
 
+    /// Interest received but not yet claimed by depositors
+    pub fn get_interest_balance(&self) -> U256 {
+        self.interest_balance.get_or_default()
+    }
+
     /// Calculate pending collateral gain for user
-    pub fn get_pending_collateral_gain(&self, user: Address) -> u64 {
-        let deposit = self.deposits.get(&user).unwrap_or(0);
-        if deposit == 0 { return 0; }
-        
-        let cum_coll = self.cumulative_collateral_per_unit.get_or_default();
-        let user_snap = self.user_collateral_snapshot.get(&user).unwrap_or(0);
-        
-        (deposit * (cum_coll - user_snap)) / SCALE_FACTOR
+    pub fn get_pending_collateral_gain(&self, user: Address) -> U256 {
+        self.gain_from_sums(user, &self.epoch_to_scale_to_sum, |snapshot| snapshot.s)
+    }
+
+    /// Calculate pending interest gain for user
+    pub fn get_pending_interest_gain(&self, user: Address) -> U256 {
+        self.gain_from_sums(user, &self.epoch_to_scale_to_b, |snapshot| snapshot.b)
+    }
+
+    /// Running product of (1 - loss per unit), 18 decimals
+    pub fn get_p(&self) -> U256 {
+        self.p.get_or_default()
+    }
+
+    pub fn get_current_scale(&self) -> u64 {
+        self.current_scale.get_or_default()
     }
 
-    /// Get effective APY from liquidation gains
-    /// This is the "real yield" that makes Stability Pool attractive
+    pub fn get_current_epoch(&self) -> u64 {
+        self.current_epoch.get_or_default()
+    }
+
+    /// Trailing APY of a deposit, 9 decimals (1e9 = 100%)
+    /// 
+    /// Liquidation discounts, at the price they were offset at, plus
+    /// interest over the last `APY_WINDOW_DAYS` days - today included -
+    /// annualized. Losses of deposits are not netted out.
     pub fn get_effective_apy(&self) -> u64 {
-        // Simplified: based on recent liquidation activity
-        // In production, would track historical gains
-        0
+        let today = self.env().get_block_time_secs() / SECONDS_PER_DAY;
+        let apy = mul_div(
+            self.gains.sum(today),
+            U256::from(365),
+            U256::from(APY_WINDOW_DAYS) * (DECIMAL_PRECISION / DECIMALS),
+            Rounding::Down
+        ).unwrap_or(U256::MAX);
+        if apy > U256::from(u64::MAX) { u64::MAX } else { apy.as_u64() }
+    }
+
+    /// Per-unit gains recorded on `day` (days since the epoch), 18 decimals
+    pub fn get_daily_gain(&self, day: u64) -> U256 {
+        self.gains.daily_gain(day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_stcspr::{MockStCSPR, MockStCSPRHostRef};
+    use crate::stablecoin::{CasperUSD, CasperUSDHostRef};
+    use odra::host::{Deployer, HostEnv, NoArgs};
+    use odra::prelude::Addressable;
+
+    const CUSD: u64 = 1_000_000_000;
+    const PRICE: u64 = 50_000_000; // $0.05 per stCSPR
+
+    // The deployer stands in for the TroveManager
+    struct Pool {
+        env: HostEnv,
+        cusd: CasperUSDHostRef,
+        stcspr: MockStCSPRHostRef,
+        pool: StabilityPoolHostRef,
+    }
+
+    fn setup() -> Pool {
+        let env = odra_test::env();
+        let manager = env.get_account(0);
+        let mut cusd = CasperUSD::deploy(&env, NoArgs);
+        cusd.add_minter(manager);
+        let stcspr = MockStCSPR::deploy(&env, NoArgs);
+        let pool = StabilityPool::deploy(&env, StabilityPoolInitArgs {
+            stablecoin: cusd.address(),
+            stcspr: stcspr.address(),
+            trove_manager: manager,
+        });
+        Pool { env, cusd, stcspr, pool }
+    }
+
+    fn deposit(sys: &mut Pool, account: usize, amount: u64) -> Address {
+        let user = sys.env.get_account(account);
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.cusd.mint(user, U256::from(amount));
+        sys.env.set_caller(user);
+        sys.cusd.approve(sys.pool.address(), U256::from(amount));
+        sys.pool.deposit(U256::from(amount));
+        user
+    }
+
+    /// Burn `debt` from the pool and hand it `collateral`, as a liquidation does
+    fn liquidate(sys: &mut Pool, debt: u64, collateral: u64) {
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.cusd.burn(sys.pool.address(), U256::from(debt));
+        sys.stcspr.faucet();
+        sys.stcspr.transfer(sys.pool.address(), U256::from(collateral));
+        sys.pool.offset(U256::from(debt), U256::from(collateral), U256::from(PRICE));
+    }
+
+    #[test]
+    fn deposits_are_held_in_cusd() {
+        let mut sys = setup();
+        let alice = deposit(&mut sys, 1, 100 * CUSD);
+        assert_eq!(sys.cusd.balance_of(sys.pool.address()), U256::from(100 * CUSD));
+        assert!(sys.cusd.balance_of(alice).is_zero());
+
+        sys.env.set_caller(alice);
+        assert_eq!(sys.pool.try_withdraw(U256::from(101 * CUSD)), Err(Error::InsufficientDeposit.into()));
+        sys.pool.withdraw(U256::from(40 * CUSD));
+        assert_eq!(sys.cusd.balance_of(sys.pool.address()), U256::from(60 * CUSD));
+        assert_eq!(sys.cusd.balance_of(alice), U256::from(40 * CUSD));
+
+        // Nothing is credited without the cUSD to back it
+        assert_eq!(
+            sys.pool.try_deposit(U256::from(CUSD)),
+            Err(crate::stablecoin::Error::InsufficientAllowance.into())
+        );
+        assert_eq!(sys.pool.get_total_deposits(), U256::from(60 * CUSD));
+    }
+
+    #[test]
+    fn losses_compound_across_offsets() {
+        let mut sys = setup();
+        let alice = deposit(&mut sys, 1, 100 * CUSD);
+        liquidate(&mut sys, 50 * CUSD, 1_000 * CUSD);
+        let bob = deposit(&mut sys, 2, 50 * CUSD);
+        liquidate(&mut sys, 50 * CUSD, 1_000 * CUSD);
+        assert!(sys.env.emitted_event(&sys.pool, StabilityDeposit {
+            depositor: bob,
+            amount: U256::from(50 * CUSD),
+            deposit: U256::from(50 * CUSD),
+        }));
+
+        // Each offset halves every deposit in the pool at the time
+        assert_eq!(sys.pool.get_deposit(alice), U256::from(25 * CUSD));
+        assert_eq!(sys.pool.get_deposit(bob), U256::from(25 * CUSD));
+        assert_eq!(sys.pool.get_pending_collateral_gain(alice), U256::from(1_500 * CUSD));
+        assert_eq!(sys.pool.get_pending_collateral_gain(bob), U256::from(500 * CUSD));
+
+        sys.env.set_caller(alice);
+        sys.pool.withdraw(U256::from(25 * CUSD));
+        assert!(sys.env.emitted_event(&sys.pool, CollateralGainClaimed {
+            depositor: alice,
+            collateral: U256::from(1_500 * CUSD),
+            deposit_loss: U256::from(75 * CUSD),
+        }));
+        assert_eq!(sys.cusd.balance_of(alice), U256::from(25 * CUSD));
+        assert_eq!(sys.stcspr.balance_of(alice), U256::from(1_500 * CUSD));
+        assert!(sys.pool.get_deposit(alice).is_zero());
+        assert_eq!(sys.pool.get_total_deposits(), U256::from(25 * CUSD));
+    }
+
+    #[test]
+    fn emptied_pool_starts_a_new_epoch() {
+        let mut sys = setup();
+        let alice = deposit(&mut sys, 1, 100 * CUSD);
+        liquidate(&mut sys, 100 * CUSD, 2_000 * CUSD);
+
+        assert_eq!(sys.pool.get_current_epoch(), 1);
+        assert_eq!(sys.pool.get_p(), U256::from(DECIMAL_PRECISION));
+        assert!(sys.pool.get_deposit(alice).is_zero());
+        assert_eq!(sys.pool.get_pending_collateral_gain(alice), U256::from(2_000 * CUSD));
+
+        // Deposits in the new epoch aren't touched by the old one
+        let bob = deposit(&mut sys, 2, 40 * CUSD);
+        liquidate(&mut sys, 10 * CUSD, 300 * CUSD);
+        assert_eq!(sys.pool.get_deposit(bob), U256::from(30 * CUSD));
+        assert_eq!(sys.pool.get_pending_collateral_gain(bob), U256::from(300 * CUSD));
+        assert_eq!(sys.pool.get_pending_collateral_gain(alice), U256::from(2_000 * CUSD));
+
+        sys.env.set_caller(alice);
+        sys.pool.claim_rewards();
+        assert_eq!(sys.stcspr.balance_of(alice), U256::from(2_000 * CUSD));
+        assert_eq!(sys.pool.get_collateral_balance(), U256::from(300 * CUSD));
+    }
+
+    #[test]
+    fn deposits_survive_a_rescale_of_p() {
+        let mut sys = setup();
+        let alice = deposit(&mut sys, 1, 1_000 * CUSD);
+        // Leaves a hundred-millionth of the pool: P drops to 1e10
+        liquidate(&mut sys, 1_000 * CUSD - 10_000, 1_000 * CUSD);
+        assert_eq!(sys.pool.get_p(), U256::from(10_000_000_000u64));
+        assert_eq!(sys.pool.get_deposit(alice), U256::from(10_000));
+
+        // A 95% loss would take P below SCALE_FACTOR, so it's rescaled
+        let carol = deposit(&mut sys, 3, 100 * CUSD);
+        liquidate(&mut sys, 95 * CUSD, 2_000 * CUSD);
+        assert_eq!(sys.pool.get_current_scale(), 1);
+        let carol_deposit = sys.pool.get_deposit(carol);
+        assert!(carol_deposit > U256::from(5 * CUSD) && carol_deposit < U256::from(5_001_000_000u64));
+        assert!(sys.pool.get_deposit(alice) < U256::from(1_000));
+
+        // Gains at the new scale still reach deposits snapshotted at the old one
+        liquidate(&mut sys, CUSD, 100 * CUSD);
+        let carol_gain = sys.pool.get_pending_collateral_gain(carol);
+        assert!(carol_gain > U256::from(2_099 * CUSD) && carol_gain <= U256::from(2_100 * CUSD));
+        let gains = carol_gain + sys.pool.get_pending_collateral_gain(alice);
+        assert!(gains <= sys.pool.get_collateral_balance());
+    }
+
+    #[test]
+    fn interest_is_shared_by_compounded_deposit() {
+        let mut sys = setup();
+        let alice = deposit(&mut sys, 1, 100 * CUSD);
+        liquidate(&mut sys, 50 * CUSD, 1_000 * CUSD);
+        let bob = deposit(&mut sys, 2, 50 * CUSD);
+
+        // Minted to the pool by the TroveManager
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.cusd.mint(sys.pool.address(), U256::from(10 * CUSD));
+        sys.pool.receive_interest(U256::from(10 * CUSD));
+
+        assert_eq!(sys.pool.get_pending_interest_gain(alice), U256::from(5 * CUSD));
+        assert_eq!(sys.pool.get_pending_interest_gain(bob), U256::from(5 * CUSD));
+
+        sys.env.set_caller(bob);
+        sys.pool.claim_rewards();
+        assert_eq!(sys.cusd.balance_of(bob), U256::from(5 * CUSD));
+        assert!(sys.pool.get_pending_interest_gain(bob).is_zero());
+        assert_eq!(sys.pool.get_deposit(bob), U256::from(50 * CUSD));
+    }
+
+    #[test]
+    fn effective_apy_tracks_a_trailing_window() {
+        let mut sys = setup();
+        deposit(&mut sys, 1, 100 * CUSD);
+        assert_eq!(sys.pool.get_effective_apy(), 0);
+
+        // 240 stCSPR at $0.05 for 10 cUSD of debt: a 2 cUSD gain on 100
+        liquidate(&mut sys, 10 * CUSD, 240 * CUSD);
+        // 0.9 cUSD of interest on the remaining 90
+        sys.env.advance_block_time(SECONDS_PER_DAY * 1000);
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.cusd.mint(sys.pool.address(), U256::from(9 * CUSD / 10));
+        sys.pool.receive_interest(U256::from(9 * CUSD / 10));
+
+        // 3% over 30 days, annualized
+        assert_eq!(sys.pool.get_effective_apy(), 365_000_000);
+
+        // The liquidation drops out of the window first
+        sys.env.advance_block_time(SECONDS_PER_DAY * (APY_WINDOW_DAYS - 1) * 1000);
+        assert_eq!(sys.pool.get_effective_apy(), 121_666_666);
+        sys.env.advance_block_time(SECONDS_PER_DAY * 1000);
+        assert_eq!(sys.pool.get_effective_apy(), 0);
     }
 }
@@ -1,18 +1,100 @@
 //! CasperUSD (cUSD) Stablecoin Token
 //! 
-//! CEP-18 compatible stablecoin with controlled minting.
-//! Only the TroveManager can mint/burn tokens.
+//! CEP-18 stablecoin with controlled minting:
+//! - U256 balances, allowances and supply
+//! - CEP-18 entry points, events and error codes
+//! - Only authorized minters (the TroveManager) can mint/burn tokens
 
+use odra::casper_types::U256;
 use odra::prelude::*;
 
-#[odra::module]
+/// cUSD errors (CEP-18 codes, plus 200-299 for admin)
+#[odra::odra_error]
+pub enum Error {
+    NotOwner = 200,
+    InsufficientBalance = 60_001,
+    InsufficientAllowance = 60_002,
+    Overflow = 60_003,
+    InsufficientRights = 60_010,
+    CannotTargetSelfUser = 60_017,
+}
+
+/// cUSD minted to `recipient`
+#[odra::event]
+pub struct Mint {
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+/// cUSD burned from `owner`
+#[odra::event]
+pub struct Burn {
+    pub owner: Address,
+    pub amount: U256,
+}
+
+/// `spender` allowance over `owner`'s balance set
+#[odra::event]
+pub struct SetAllowance {
+    pub owner: Address,
+    pub spender: Address,
+    pub allowance: U256,
+}
+
+/// `spender` allowance over `owner`'s balance raised by `inc_by`
+#[odra::event]
+pub struct IncreaseAllowance {
+    pub owner: Address,
+    pub spender: Address,
+    pub allowance: U256,
+    pub inc_by: U256,
+}
+
+/// `spender` allowance over `owner`'s balance lowered by `decr_by`
+#[odra::event]
+pub struct DecreaseAllowance {
+    pub owner: Address,
+    pub spender: Address,
+    pub allowance: U256,
+    pub decr_by: U256,
+}
+
+/// Balance moved by its owner
+#[odra::event]
+pub struct Transfer {
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+/// Balance moved by `spender` out of its allowance
+#[odra::event]
+pub struct TransferFrom {
+    pub spender: Address,
+    pub owner: Address,
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+#[odra::module(
+    events = [
+        Mint,
+        Burn,
+        SetAllowance,
+        IncreaseAllowance,
+        DecreaseAllowance,
+        Transfer,
+        TransferFrom
+    ],
+    errors = Error
+)]
 pub struct CasperUSD {
     name: Var<String>,
     symbol: Var<String>,
     decimals: Var<u8>,
-    total_supply: Var<u64>,
-    balances: Mapping<Address, u64>,
-    allowances: Mapping<(Address, Address), u64>,
+    total_supply: Var<U256>,
+    balances: Mapping<Address, U256>,
+    allowances: Mapping<(Address, Address), U256>,
     // Authorized minters (TroveManager, StabilityPool)
     minters: Mapping<Address, bool>,
     owner: Var<Address>,
@@ -25,7 +107,7 @@ impl CasperUSD {
         self.name.set(String::from("CasperUSD"));
         self.symbol.set(String::from("cUSD"));
         self.decimals.set(9);
-        self.total_supply.set(0);
+        self.total_supply.set(U256::zero());
         self.owner.set(caller);
         self.minters.set(&caller, true); // Owner is initial minter
     }
@@ -42,29 +124,41 @@ impl CasperUSD {
         self.minters.set(&minter, false);
     }
 
+    pub fn is_minter(&self, address: Address) -> bool {
+        self.minters.get(&address).unwrap_or(false)
+    }
+
     /// Mint new cUSD - only authorized minters
-    pub fn mint(&mut self, to: Address, amount: u64) {
-        let caller = self.env().caller();
-        assert!(self.minters.get(&caller).unwrap_or(false), "Not authorized minter");
-        
-        let balance = self.balances.get(&to).unwrap_or(0);
-        self.balances.set(&to, balance + amount);
+    pub fn mint(&mut self, owner: Address, amount: U256) {
+        self.only_minter();
         
         let supply = self.total_supply.get_or_default();
-        self.total_supply.set(supply + amount);
+        let new_supply = supply
+            .checked_add(amount)
+            .unwrap_or_revert_with(self, Error::Overflow);
+        self.total_supply.set(new_supply);
+        
+        // Can't overflow once the supply didn't
+        let balance = self.balance_of(owner);
+        self.balances.set(&owner, balance + amount);
+        
+        self.env().emit_event(Mint { recipient: owner, amount });
     }
 
     /// Burn cUSD - only authorized minters
-    pub fn burn(&mut self, from: Address, amount: u64) {
-        let caller = self.env().caller();
-        assert!(self.minters.get(&caller).unwrap_or(false), "Not authorized minter");
+    pub fn burn(&mut self, owner: Address, amount: U256) {
+        self.only_minter();
         
-        let balance = self.balances.get(&from).unwrap_or(0);
-        assert!(balance >= amount, "Insufficient balance to burn");
-        self.balances.set(&from, balance - amount);
+        let balance = self.balance_of(owner);
+        if balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
+        self.balances.set(&owner, balance - amount);
         
         let supply = self.total_supply.get_or_default();
         self.total_supply.set(supply - amount);
+        
+        self.env().emit_event(Burn { owner, amount });
     }
 
     // === CEP-18 Standard Functions ===
@@ -81,47 +175,223 @@ impl CasperUSD {
         self.decimals.get_or_default()
     }
 
-    pub fn total_supply(&self) -> u64 {
+    pub fn total_supply(&self) -> U256 {
         self.total_supply.get_or_default()
     }
 
-    pub fn balance_of(&self, address: Address) -> u64 {
-        self.balances.get(&address).unwrap_or(0)
+    pub fn balance_of(&self, address: Address) -> U256 {
+        self.balances.get(&address).unwrap_or_default()
     }
 
-    pub fn allowance(&self, owner: Address, spender: Address) -> u64 {
-        self.allowances.get(&(owner, spender)).unwrap_or(0)
+    pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        self.allowances.get(&(owner, spender)).unwrap_or_default()
     }
 
-    pub fn transfer(&mut self, to: Address, amount: u64) {
-        let caller = self.env().caller();
-        self.internal_transfer(caller, to, amount);
+    pub fn transfer(&mut self, recipient: Address, amount: U256) {
+        let sender = self.env().caller();
+        if sender == recipient {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+        
+        self.raw_transfer(sender, recipient, amount);
+        
+        self.env().emit_event(Transfer { sender, recipient, amount });
     }
 
-    pub fn approve(&mut self, spender: Address, amount: u64) {
-        let caller = self.env().caller();
-        self.allowances.set(&(caller, spender), amount);
+    pub fn approve(&mut self, spender: Address, amount: U256) {
+        let owner = self.env().caller();
+        if owner == spender {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+        
+        self.allowances.set(&(owner, spender), amount);
+        
+        self.env().emit_event(SetAllowance { owner, spender, allowance: amount });
     }
 
-    pub fn transfer_from(&mut self, from: Address, to: Address, amount: u64) {
-        let caller = self.env().caller();
-        let allowance = self.allowances.get(&(from, caller)).unwrap_or(0);
-        assert!(allowance >= amount, "Insufficient allowance");
+    /// Raise an allowance, saturating at the U256 maximum
+    pub fn increase_allowance(&mut self, spender: Address, inc_by: U256) {
+        let owner = self.env().caller();
+        if owner == spender {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
         
-        self.allowances.set(&(from, caller), allowance - amount);
-        self.internal_transfer(from, to, amount);
+        let allowance = self.allowance(owner, spender).saturating_add(inc_by);
+        self.allowances.set(&(owner, spender), allowance);
+        
+        self.env().emit_event(IncreaseAllowance { owner, spender, allowance, inc_by });
+    }
+
+    /// Lower an allowance, saturating at zero
+    pub fn decrease_allowance(&mut self, spender: Address, decr_by: U256) {
+        let owner = self.env().caller();
+        if owner == spender {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+        
+        let allowance = self.allowance(owner, spender).saturating_sub(decr_by);
+        self.allowances.set(&(owner, spender), allowance);
+        
+        self.env().emit_event(DecreaseAllowance { owner, spender, allowance, decr_by });
+    }
+
+    pub fn transfer_from(&mut self, owner: Address, recipient: Address, amount: U256) {
+        let spender = self.env().caller();
+        if owner == recipient {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+        if amount.is_zero() { return; }
+        
+        let allowance = self.allowance(owner, spender);
+        if allowance < amount {
+            self.env().revert(Error::InsufficientAllowance);
+        }
+        self.allowances.set(&(owner, spender), allowance - amount);
+        
+        self.raw_transfer(owner, recipient, amount);
+        
+        self.env().emit_event(TransferFrom { spender, owner, recipient, amount });
     }
 
-    fn internal_transfer(&mut self, from: Address, to: Address, amount: u64) {
-        let from_balance = self.balances.get(&from).unwrap_or(0);
-        assert!(from_balance >= amount, "Insufficient balance");
+    fn raw_transfer(&mut self, sender: Address, recipient: Address, amount: U256) {
+        let sender_balance = self.balance_of(sender);
+        if sender_balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
         
-        self.balances.set(&from, from_balance - amount);
-        let to_balance = self.balances.get(&to).unwrap_or(0);
-        self.balances.set(&to, to_balance + amount);
+        self.balances.set(&sender, sender_balance - amount);
+        let recipient_balance = self.balance_of(recipient);
+        self.balances.set(&recipient, recipient_balance + amount);
+    }
+
+    fn only_minter(&self) {
+        if !self.is_minter(self.env().caller()) {
+            self.env().revert(Error::InsufficientRights);
+        }
     }
 
     fn only_owner(&self) {
-        assert!(self.env().caller() == self.owner.get().unwrap(), "Only owner");
+        if self.env().caller() != self.owner.get().unwrap() {
+            self.env().revert(Error::NotOwner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::host::{Deployer, HostEnv, NoArgs};
+
+    struct Token {
+        env: HostEnv,
+        cusd: CasperUSDHostRef,
+        alice: Address,
+        bob: Address,
+    }
+
+    /// Deploy from the default account and mint 1,000 to alice
+    fn setup() -> Token {
+        let env = odra_test::env();
+        let mut cusd = CasperUSD::deploy(&env, NoArgs);
+        let alice = env.get_account(1);
+        let bob = env.get_account(2);
+        cusd.mint(alice, U256::from(1_000));
+        Token { env, cusd, alice, bob }
+    }
+
+    #[test]
+    fn allowances_move_and_saturate() {
+        let mut t = setup();
+        t.env.set_caller(t.alice);
+        t.cusd.approve(t.bob, U256::from(100));
+        t.cusd.increase_allowance(t.bob, U256::from(50));
+        assert_eq!(t.cusd.allowance(t.alice, t.bob), U256::from(150));
+        t.cusd.decrease_allowance(t.bob, U256::from(30));
+        assert_eq!(t.cusd.allowance(t.alice, t.bob), U256::from(120));
+        assert!(t.env.emitted_event(&t.cusd, SetAllowance {
+            owner: t.alice,
+            spender: t.bob,
+            allowance: U256::from(100),
+        }));
+        assert!(t.env.emitted_event(&t.cusd, IncreaseAllowance {
+            owner: t.alice,
+            spender: t.bob,
+            allowance: U256::from(150),
+            inc_by: U256::from(50),
+        }));
+        assert!(t.env.emitted_event(&t.cusd, DecreaseAllowance {
+            owner: t.alice,
+            spender: t.bob,
+            allowance: U256::from(120),
+            decr_by: U256::from(30),
+        }));
+
+        // Lowering past zero leaves zero, raising past the maximum leaves the maximum
+        t.cusd.decrease_allowance(t.bob, U256::from(1_000));
+        assert!(t.cusd.allowance(t.alice, t.bob).is_zero());
+        t.cusd.increase_allowance(t.bob, U256::MAX);
+        t.cusd.increase_allowance(t.bob, U256::one());
+        assert_eq!(t.cusd.allowance(t.alice, t.bob), U256::MAX);
+    }
+
+    #[test]
+    fn transfers_spend_balances_and_allowances() {
+        let mut t = setup();
+        let carol = t.env.get_account(3);
+        t.env.set_caller(t.alice);
+        t.cusd.transfer(carol, U256::from(200));
+        t.cusd.approve(t.bob, U256::from(300));
+        assert!(t.env.emitted_event(&t.cusd, Transfer {
+            sender: t.alice,
+            recipient: carol,
+            amount: U256::from(200),
+        }));
+
+        t.env.set_caller(t.bob);
+        assert_eq!(
+            t.cusd.try_transfer_from(t.alice, carol, U256::from(301)),
+            Err(Error::InsufficientAllowance.into())
+        );
+        t.cusd.transfer_from(t.alice, carol, U256::from(250));
+        assert_eq!(t.cusd.balance_of(t.alice), U256::from(550));
+        assert_eq!(t.cusd.balance_of(carol), U256::from(450));
+        assert_eq!(t.cusd.allowance(t.alice, t.bob), U256::from(50));
+        assert!(t.env.emitted_event(&t.cusd, TransferFrom {
+            spender: t.bob,
+            owner: t.alice,
+            recipient: carol,
+            amount: U256::from(250),
+        }));
+    }
+
+    #[test]
+    fn minting_and_burning_need_rights() {
+        let mut t = setup();
+        assert!(t.env.emitted_event(&t.cusd, Mint { recipient: t.alice, amount: U256::from(1_000) }));
+        t.cusd.burn(t.alice, U256::from(400));
+        assert!(t.env.emitted_event(&t.cusd, Burn { owner: t.alice, amount: U256::from(400) }));
+        assert_eq!(t.cusd.total_supply(), U256::from(600));
+
+        t.env.set_caller(t.alice);
+        assert_eq!(t.cusd.try_mint(t.alice, U256::one()), Err(Error::InsufficientRights.into()));
+        assert_eq!(t.cusd.try_burn(t.alice, U256::one()), Err(Error::InsufficientRights.into()));
+        assert_eq!(t.cusd.try_add_minter(t.alice), Err(Error::NotOwner.into()));
+    }
+
+    #[test]
+    fn cep18_error_codes() {
+        let mut t = setup();
+        assert_eq!(t.cusd.try_burn(t.bob, U256::one()), Err(Error::InsufficientBalance.into()));
+        assert_eq!(t.cusd.try_mint(t.bob, U256::MAX), Err(Error::Overflow.into()));
+
+        t.env.set_caller(t.alice);
+        assert_eq!(t.cusd.try_transfer(t.alice, U256::one()), Err(Error::CannotTargetSelfUser.into()));
+        assert_eq!(t.cusd.try_approve(t.alice, U256::one()), Err(Error::CannotTargetSelfUser.into()));
+        let err = t.cusd.try_transfer(t.bob, U256::from(1_001)).unwrap_err();
+        assert_eq!(err.code(), 60_001);
+
+        t.env.set_caller(t.bob);
+        assert_eq!(t.cusd.try_transfer_from(t.alice, t.bob, U256::one()).unwrap_err().code(), 60_002);
+        assert_eq!(t.cusd.try_transfer_from(t.alice, t.alice, U256::one()).unwrap_err().code(), 60_017);
     }
 }
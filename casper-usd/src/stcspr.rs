@@ -0,0 +1,480 @@
+//! stCSPR - Liquid Staking Token
+//!
+//! CEP-18 shares over CSPR delegated to a validator:
+//! - `stake` delegates the attached CSPR and mints shares at the exchange rate
+//! - Staking rewards compound into the delegation, so the rate only grows
+//! - `request_unstake` burns shares and undelegates their CSPR
+//! - `claim` pays it out once the unbonding delay has passed
+//! - Stakes, and what an unstake leaves delegated, respect the minimum delegation
+
+use odra::casper_types::{PublicKey, U256};
+use odra::prelude::*;
+
+use crate::math::{mul_div, narrow, widen, Rounding, DECIMALS};
+
+// 7 eras of unbonding plus margin for the funds to land
+const UNBONDING_DELAY: u64 = 16 * 3600;
+// Casper's default minimum delegation, 500 CSPR. Undelegating below it
+// unbonds the whole delegation.
+const MIN_DELEGATION: u64 = 500_000_000_000;
+
+/// Pending withdrawal of undelegated CSPR
+#[odra::odra_type]
+pub struct UnstakeRequest {
+    pub owner: Address,
+    pub cspr: U256,
+    pub unlock_at: u64,
+    pub claimed: bool,
+}
+
+/// stCSPR errors (CEP-18 codes, plus 700-799 for staking)
+#[odra::odra_error]
+pub enum Error {
+    ZeroAmount = 700,
+    RequestNotFound = 701,
+    NotRequestOwner = 702,
+    AlreadyClaimed = 703,
+    StillUnbonding = 704,
+    FundsNotUnbonded = 705,
+    MathOverflow = 706,
+    BelowMinimumDelegation = 707,
+    InsufficientBalance = 60_001,
+    InsufficientAllowance = 60_002,
+    CannotTargetSelfUser = 60_017,
+}
+
+/// CSPR staked for freshly minted shares
+#[odra::event]
+pub struct Stake {
+    pub staker: Address,
+    pub cspr: U256,
+    pub shares: U256,
+}
+
+/// Shares burned, their CSPR unbonding until `unlock_at`
+#[odra::event]
+pub struct UnstakeRequested {
+    pub owner: Address,
+    pub request_id: u64,
+    pub shares: U256,
+    pub cspr: U256,
+    pub unlock_at: u64,
+}
+
+/// Unbonded CSPR paid out
+#[odra::event]
+pub struct UnstakeClaimed {
+    pub owner: Address,
+    pub request_id: u64,
+    pub cspr: U256,
+}
+
+/// stCSPR minted to `recipient`
+#[odra::event]
+pub struct Mint {
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+/// stCSPR burned from `owner`
+#[odra::event]
+pub struct Burn {
+    pub owner: Address,
+    pub amount: U256,
+}
+
+/// `spender` allowance over `owner`'s balance set
+#[odra::event]
+pub struct SetAllowance {
+    pub owner: Address,
+    pub spender: Address,
+    pub allowance: U256,
+}
+
+/// Balance moved by its owner
+#[odra::event]
+pub struct Transfer {
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+/// Balance moved by `spender` out of its allowance
+#[odra::event]
+pub struct TransferFrom {
+    pub spender: Address,
+    pub owner: Address,
+    pub recipient: Address,
+    pub amount: U256,
+}
+
+#[odra::module(
+    events = [
+        Stake,
+        UnstakeRequested,
+        UnstakeClaimed,
+        Mint,
+        Burn,
+        SetAllowance,
+        Transfer,
+        TransferFrom
+    ],
+    errors = Error
+)]
+pub struct StCSPR {
+    name: Var<String>,
+    symbol: Var<String>,
+    decimals: Var<u8>,
+    total_supply: Var<U256>,
+    balances: Mapping<Address, U256>,
+    allowances: Mapping<(Address, Address), U256>,
+
+    // Validator all CSPR is delegated to
+    validator: Var<PublicKey>,
+
+    // Unstake queue
+    unstake_requests: Mapping<u64, UnstakeRequest>,
+    next_request_id: Var<u64>,
+    // Undelegated CSPR owed to unclaimed requests
+    total_unstaking: Var<U256>,
+}
+
+#[odra::module]
+impl StCSPR {
+    pub fn init(&mut self, validator: PublicKey) {
+        self.name.set(String::from("Staked CSPR"));
+        self.symbol.set(String::from("stCSPR"));
+        self.decimals.set(9);
+        self.total_supply.set(U256::zero());
+        self.validator.set(validator);
+        self.next_request_id.set(0);
+        self.total_unstaking.set(U256::zero());
+    }
+
+    // === STAKING ===
+
+    /// Stake the attached CSPR for stCSPR at the current exchange rate
+    #[odra(payable)]
+    pub fn stake(&mut self) {
+        let staker = self.env().caller();
+        let cspr = narrow(self.env().attached_value())
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        if cspr.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+        if cspr < U256::from(MIN_DELEGATION) {
+            self.env().revert(Error::BelowMinimumDelegation);
+        }
+
+        // Price shares before the new CSPR joins the pool, rounding against the staker
+        let supply = self.total_supply();
+        let pooled = self.total_pooled();
+        let shares = if supply.is_zero() || pooled.is_zero() {
+            cspr
+        } else {
+            mul_div(cspr, supply, pooled, Rounding::Down)
+                .unwrap_or_revert_with(self, Error::MathOverflow)
+        };
+        if shares.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+
+        self.env().delegate(self.validator.get().unwrap(), widen(cspr));
+        self.mint(staker, shares);
+
+        self.env().emit_event(Stake { staker, cspr, shares });
+    }
+
+    /// Burn `shares` and start unbonding the CSPR they're worth
+    ///
+    /// Returns the request id to `claim` once the unbonding delay has passed.
+    pub fn request_unstake(&mut self, shares: U256) -> u64 {
+        let owner = self.env().caller();
+        if shares.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+
+        let pooled = self.total_pooled();
+        let cspr = mul_div(shares, pooled, self.total_supply(), Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        if cspr.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+        // Exit fully or leave enough for the validator to keep the rest
+        let remaining = pooled - cspr;
+        if !remaining.is_zero() && remaining < U256::from(MIN_DELEGATION) {
+            self.env().revert(Error::BelowMinimumDelegation);
+        }
+        self.burn(owner, shares);
+        self.env().undelegate(self.validator.get().unwrap(), widen(cspr));
+
+        let request_id = self.next_request_id.get_or_default();
+        let unlock_at = self.env().get_block_time_secs() + UNBONDING_DELAY;
+        self.unstake_requests.set(&request_id, UnstakeRequest {
+            owner,
+            cspr,
+            unlock_at,
+            claimed: false,
+        });
+        self.next_request_id.set(request_id + 1);
+        self.total_unstaking.set(self.total_unstaking.get_or_default() + cspr);
+
+        self.env().emit_event(UnstakeRequested { owner, request_id, shares, cspr, unlock_at });
+        request_id
+    }
+
+    /// Pay out an unstake request after the unbonding delay
+    pub fn claim(&mut self, request_id: u64) {
+        let caller = self.env().caller();
+        let mut request = self.unstake_requests
+            .get(&request_id)
+            .unwrap_or_revert_with(self, Error::RequestNotFound);
+        if request.owner != caller {
+            self.env().revert(Error::NotRequestOwner);
+        }
+        if request.claimed {
+            self.env().revert(Error::AlreadyClaimed);
+        }
+        if self.env().get_block_time_secs() < request.unlock_at {
+            self.env().revert(Error::StillUnbonding);
+        }
+        if self.env().self_balance() < widen(request.cspr) {
+            self.env().revert(Error::FundsNotUnbonded);
+        }
+
+        request.claimed = true;
+        let cspr = request.cspr;
+        self.unstake_requests.set(&request_id, request);
+        self.total_unstaking.set(self.total_unstaking.get_or_default() - cspr);
+        self.env().transfer_tokens(&caller, &widen(cspr));
+
+        self.env().emit_event(UnstakeClaimed { owner: caller, request_id, cspr });
+    }
+
+    // === VIEW FUNCTIONS ===
+
+    /// CSPR backing one stCSPR, 9 decimals
+    pub fn exchange_rate(&self) -> U256 {
+        let supply = self.total_supply();
+        if supply.is_zero() {
+            return U256::from(DECIMALS);
+        }
+        mul_div(self.total_pooled(), U256::from(DECIMALS), supply, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    /// CSPR backing all stCSPR - the delegation, rewards included
+    pub fn total_pooled(&self) -> U256 {
+        narrow(self.env().delegated_amount(self.validator.get().unwrap()))
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+    }
+
+    pub fn get_validator(&self) -> PublicKey {
+        self.validator.get().unwrap()
+    }
+
+    pub fn get_unstake_request(&self, request_id: u64) -> Option<UnstakeRequest> {
+        self.unstake_requests.get(&request_id)
+    }
+
+    pub fn get_total_unstaking(&self) -> U256 {
+        self.total_unstaking.get_or_default()
+    }
+
+    // === CEP-18 Standard Functions ===
+
+    pub fn name(&self) -> String {
+        self.name.get_or_default()
+    }
+
+    pub fn symbol(&self) -> String {
+        self.symbol.get_or_default()
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals.get_or_default()
+    }
+
+    pub fn total_supply(&self) -> U256 {
+        self.total_supply.get_or_default()
+    }
+
+    pub fn balance_of(&self, address: Address) -> U256 {
+        self.balances.get(&address).unwrap_or_default()
+    }
+
+    pub fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        self.allowances.get(&(owner, spender)).unwrap_or_default()
+    }
+
+    pub fn transfer(&mut self, recipient: Address, amount: U256) {
+        let sender = self.env().caller();
+        if sender == recipient {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+
+        self.raw_transfer(sender, recipient, amount);
+
+        self.env().emit_event(Transfer { sender, recipient, amount });
+    }
+
+    pub fn approve(&mut self, spender: Address, amount: U256) {
+        let owner = self.env().caller();
+        if owner == spender {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+
+        self.allowances.set(&(owner, spender), amount);
+
+        self.env().emit_event(SetAllowance { owner, spender, allowance: amount });
+    }
+
+    pub fn transfer_from(&mut self, owner: Address, recipient: Address, amount: U256) {
+        let spender = self.env().caller();
+        if owner == recipient {
+            self.env().revert(Error::CannotTargetSelfUser);
+        }
+        if amount.is_zero() { return; }
+
+        let allowance = self.allowance(owner, spender);
+        if allowance < amount {
+            self.env().revert(Error::InsufficientAllowance);
+        }
+        self.allowances.set(&(owner, spender), allowance - amount);
+
+        self.raw_transfer(owner, recipient, amount);
+
+        self.env().emit_event(TransferFrom { spender, owner, recipient, amount });
+    }
+
+    // === INTERNAL ===
+
+    fn mint(&mut self, owner: Address, amount: U256) {
+        self.total_supply.set(self.total_supply() + amount);
+        self.balances.set(&owner, self.balance_of(owner) + amount);
+        self.env().emit_event(Mint { recipient: owner, amount });
+    }
+
+    fn burn(&mut self, owner: Address, amount: U256) {
+        let balance = self.balance_of(owner);
+        if balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
+        self.balances.set(&owner, balance - amount);
+        self.total_supply.set(self.total_supply() - amount);
+        self.env().emit_event(Burn { owner, amount });
+    }
+
+    fn raw_transfer(&mut self, sender: Address, recipient: Address, amount: U256) {
+        let sender_balance = self.balance_of(sender);
+        if sender_balance < amount {
+            self.env().revert(Error::InsufficientBalance);
+        }
+
+        self.balances.set(&sender, sender_balance - amount);
+        let recipient_balance = self.balance_of(recipient);
+        self.balances.set(&recipient, recipient_balance + amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use odra::casper_types::U512;
+    use odra::host::{Deployer, HostEnv, HostRef};
+
+    const CSPR: u64 = 1_000_000_000;
+
+    fn setup() -> (HostEnv, StCSPRHostRef) {
+        let env = odra_test::env();
+        let validator = env.get_validator(0);
+        let stcspr = StCSPR::deploy(&env, StCSPRInitArgs { validator });
+        (env, stcspr)
+    }
+
+    fn stake(stcspr: &mut StCSPRHostRef, cspr: u64) {
+        stcspr.with_tokens(U512::from(cspr)).stake();
+    }
+
+    #[test]
+    fn first_stake_mints_one_to_one() {
+        let (env, mut stcspr) = setup();
+        let alice = env.get_account(1);
+        env.set_caller(alice);
+        stake(&mut stcspr, 1_000 * CSPR);
+
+        assert_eq!(stcspr.balance_of(alice), U256::from(1_000 * CSPR));
+        assert_eq!(stcspr.total_pooled(), U256::from(1_000 * CSPR));
+        assert_eq!(stcspr.exchange_rate(), U256::from(DECIMALS));
+        assert_eq!(stcspr.try_stake(), Err(Error::ZeroAmount.into()));
+    }
+
+    #[test]
+    fn delegation_stays_above_the_minimum() {
+        let (env, mut stcspr) = setup();
+        let alice = env.get_account(1);
+        env.set_caller(alice);
+        assert_eq!(
+            stcspr.with_tokens(U512::from(MIN_DELEGATION - 1)).try_stake(),
+            Err(Error::BelowMinimumDelegation.into())
+        );
+        stake(&mut stcspr, 800 * CSPR);
+
+        // Leaving 300 CSPR delegated would unbond all of it
+        assert_eq!(
+            stcspr.try_request_unstake(U256::from(500 * CSPR)),
+            Err(Error::BelowMinimumDelegation.into())
+        );
+        stcspr.request_unstake(U256::from(300 * CSPR));
+        stcspr.request_unstake(U256::from(500 * CSPR));
+        assert!(stcspr.total_pooled().is_zero());
+    }
+
+    #[test]
+    fn rewards_raise_the_exchange_rate() {
+        let (env, mut stcspr) = setup();
+        let alice = env.get_account(1);
+        let bob = env.get_account(2);
+        env.set_caller(alice);
+        stake(&mut stcspr, 1_000 * CSPR);
+
+        env.advance_with_auctions(env.auction_delay() * 10);
+        let rate = stcspr.exchange_rate();
+        assert!(rate > U256::from(DECIMALS));
+
+        // Later stakers get fewer shares for the same CSPR
+        env.set_caller(bob);
+        stake(&mut stcspr, 1_000 * CSPR);
+        assert!(stcspr.balance_of(bob) < stcspr.balance_of(alice));
+        assert!(stcspr.exchange_rate() >= rate - 1);
+    }
+
+    #[test]
+    fn unstake_pays_out_after_unbonding() {
+        let (env, mut stcspr) = setup();
+        let alice = env.get_account(1);
+        let bob = env.get_account(2);
+        env.set_caller(alice);
+        stake(&mut stcspr, 1_000 * CSPR);
+        env.advance_with_auctions(env.auction_delay() * 2);
+
+        let shares = stcspr.balance_of(alice) / 2;
+        let id = stcspr.request_unstake(shares);
+        let request = stcspr.get_unstake_request(id).unwrap();
+        assert_eq!(request.owner, alice);
+        assert_eq!(stcspr.balance_of(alice), shares);
+        assert_eq!(stcspr.get_total_unstaking(), request.cspr);
+        assert_eq!(stcspr.try_claim(id), Err(Error::StillUnbonding.into()));
+
+        env.advance_with_auctions(UNBONDING_DELAY * 1_000);
+        env.set_caller(bob);
+        assert_eq!(stcspr.try_claim(id), Err(Error::NotRequestOwner.into()));
+
+        env.set_caller(alice);
+        let before = env.balance_of(&alice);
+        stcspr.claim(id);
+        assert_eq!(env.balance_of(&alice), before + widen(request.cspr));
+        assert_eq!(stcspr.get_total_unstaking(), U256::zero());
+        assert_eq!(stcspr.try_claim(id), Err(Error::AlreadyClaimed.into()));
+    }
+}
@@ -0,0 +1,21 @@
+//! External Token Interfaces
+//!
+//! Used to move stCSPR collateral in and out of protocol contracts,
+//! and to read its CSPR exchange rate.
+
+use odra::casper_types::U256;
+use odra::prelude::*;
+
+#[odra::external_contract]
+pub trait Cep18Token {
+    fn balance_of(&self, address: Address) -> U256;
+    fn transfer(&mut self, to: Address, amount: U256);
+    fn transfer_from(&mut self, from: Address, to: Address, amount: U256);
+}
+
+/// Liquid staking token - stCSPR redeemable for a growing amount of CSPR
+#[odra::external_contract]
+pub trait LiquidStakingToken {
+    /// CSPR per stCSPR, 9 decimals
+    fn exchange_rate(&self) -> U256;
+}
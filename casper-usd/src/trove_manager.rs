@@ -5,93 +5,297 @@
 //! - Lower rates = higher redemption priority
 //! - Continuous interest accrual
 //! - Soft liquidation support
+//! - Redistribution to active troves when the Stability Pool can't cover a liquidation
+//! - Recovery Mode when the system-wide collateral ratio drops below 150%
 
+use odra::casper_types::U256;
 use odra::prelude::*;
+use odra::ContractRef;
+
+use crate::aggregate_interest::{trove_interest, AggregateInterest};
+use crate::math::{dec_div, dec_mul, dec_pow, mul_div, percent_of, ratio_percent, Decimal, Rounding, DECIMALS};
+use crate::oracle_router::{OracleRouterContractRef, PriceSource};
+use crate::redistribution::Redistribution;
+use crate::sorted_troves::SortedTroves;
+use crate::stability_pool::StabilityPoolContractRef;
+use crate::stablecoin::CasperUSDContractRef;
+use crate::token::Cep18TokenContractRef;
 
-const DECIMALS: u64 = 1_000_000_000; // 9 decimals
 const MIN_COLLATERAL_RATIO: u64 = 150; // 150%
 const LIQUIDATION_RATIO: u64 = 110; // 110% - soft liquidation starts
+const CRITICAL_COLLATERAL_RATIO: u64 = 150; // 150% TCR - recovery mode below
 const MIN_DEBT: u64 = 100_000_000_000; // 100 cUSD minimum
 const MIN_INTEREST_RATE: u64 = 5_000_000; // 0.5% annual
 const MAX_INTEREST_RATE: u64 = 200_000_000_000; // 200% annual
-const SECONDS_PER_YEAR: u64 = 31536000;
 const REDEMPTION_FEE_FLOOR: u64 = 5_000_000; // 0.5%
 const BORROWING_FEE: u64 = 5_000_000; // 0.5%
+const COLL_GAS_COMPENSATION_DIVISOR: u64 = 200; // 0.5% of collateral to liquidator
+const MINUTE_DECAY_FACTOR: u64 = 999_037_759; // 12h half-life for base rate
+const REDEMPTION_BETA: u64 = 2;
+const SP_YIELD_SPLIT: u64 = 75; // 75% of interest to depositors, the rest to the treasury
 
-#[odra::module]
+/// Per-borrower position
+#[odra::odra_type]
+#[derive(Default)]
+pub struct Trove {
+    pub collateral: U256,
+    pub debt: U256,
+    pub interest_rate: u64,
+    pub last_update: u64,
+    pub active: bool,
+    // Redeemed below minimum debt - out of the sorted list until topped up
+    pub zombie: bool,
+    // Redistribution share and reward snapshots
+    pub stake: U256,
+    pub l_coll_snapshot: U256,
+    pub l_debt_snapshot: U256,
+}
+
+/// Redemption base rate and when it last moved
+#[odra::odra_type]
+#[derive(Default)]
+pub struct RedemptionRate {
+    pub base_rate: u64,
+    pub last_redemption_time: u64,
+}
+
+/// Running totals of a liquidation call
+#[odra::odra_type]
+#[derive(Default)]
+pub struct LiquidationTotals {
+    pub troves: u64,
+    pub debt: U256,
+    pub collateral: U256,
+    // Cancelled against the Stability Pool
+    pub debt_offset: U256,
+    pub collateral_offset: U256,
+    // Spread over the remaining troves
+    pub debt_redistributed: U256,
+    pub collateral_redistributed: U256,
+    pub gas_compensation: U256,
+}
+
+/// Trove Manager errors (codes 300-399)
+#[odra::odra_error]
+pub enum Error {
+    NotOwner = 300,
+    TroveAlreadyExists = 301,
+    NoActiveTrove = 302,
+    ZeroAmount = 303,
+    DebtBelowMinimum = 304,
+    InterestRateTooLow = 305,
+    InterestRateTooHigh = 306,
+    BelowMinCollateralRatio = 307,
+    BelowCriticalRatio = 308,
+    WouldTriggerRecoveryMode = 309,
+    NotAllowedInRecoveryMode = 310,
+    InsufficientCollateral = 311,
+    OutstandingDebt = 312,
+    NotLiquidatable = 313,
+    StabilityPoolTooSmall = 314,
+    FeeExceedsMaximum = 315,
+    NothingToRedeem = 316,
+    StabilityPoolNotSet = 318,
+    PriceUnavailable = 319,
+    OnlyOneTrove = 320,
+    MathOverflow = 321,
+}
+
+/// A new trove was opened
+#[odra::event]
+pub struct TroveOpened {
+    pub owner: Address,
+    pub collateral: U256,
+    pub debt: U256,
+    pub interest_rate: u64,
+    pub fee: U256,
+}
+
+/// A trove's collateral, debt or interest rate changed
+#[odra::event]
+pub struct TroveAdjusted {
+    pub owner: Address,
+    pub collateral: U256,
+    pub debt: U256,
+    pub interest_rate: u64,
+}
+
+/// A trove was closed by its owner
+#[odra::event]
+pub struct TroveClosed {
+    pub owner: Address,
+    pub collateral: U256,
+}
+
+/// A trove was liquidated
+#[odra::event]
+pub struct TroveLiquidated {
+    pub owner: Address,
+    pub liquidator: Address,
+    pub debt: U256,
+    pub collateral: U256,
+    pub debt_offset: U256,
+    pub debt_redistributed: U256,
+    pub collateral_surplus: U256,
+}
+
+/// Accrued interest was minted as cUSD
+#[odra::event]
+pub struct InterestMinted {
+    pub stability_pool: U256,
+    pub treasury: U256,
+}
+
+/// cUSD was redeemed for collateral
+#[odra::event]
+pub struct Redemption {
+    pub redeemer: Address,
+    pub cusd_amount: U256,
+    pub debt_redeemed: U256,
+    pub collateral_drawn: U256,
+    pub fee_rate: u64,
+}
+
+#[odra::module(
+    events = [TroveOpened, TroveAdjusted, TroveClosed, TroveLiquidated, InterestMinted, Redemption],
+    errors = Error
+)]
 pub struct TroveManager {
     owner: Var<Address>,
+    // OracleRouter over the primary and secondary price oracles
     oracle: Var<Address>,
     stablecoin: Var<Address>,
     stcspr_token: Var<Address>,
     stability_pool: Var<Address>,
+    // Recipient of the interest not paid to Stability Pool depositors
+    treasury: Var<Address>,
     
-    // Trove storage - separate mappings for each field
-    trove_collateral: Mapping<Address, u64>,
-    trove_debt: Mapping<Address, u64>,
-    trove_interest_rate: Mapping<Address, u64>,
-    trove_last_update: Mapping<Address, u64>,
-    trove_active: Mapping<Address, bool>,
+    // Trove storage
+    troves: Mapping<Address, Trove>,
     
     // Protocol stats
-    total_collateral: Var<u64>,
-    total_debt: Var<u64>,
+    total_collateral: Var<U256>,
+    total_debt: Var<U256>,
     trove_count: Var<u64>,
+    // Borrowing fees + interest added to debt but not minted as cUSD
+    outstanding_fees: Var<U256>,
     
     // Redemption tracking
-    base_rate: Var<u64>,
-    last_redemption_time: Var<u64>,
+    redemption_rate: Var<RedemptionRate>,
+    
+    // Liquidation redistribution (L_coll / L_debt)
+    rewards: SubModule<Redistribution>,
+    
+    // Sum of debt * rate over all troves - system-wide interest in O(1)
+    interest: SubModule<AggregateInterest>,
+    
+    // Troves ordered by interest rate - redemption order
+    sorted_troves: SubModule<SortedTroves>,
 }
 
 #[odra::module]
 impl TroveManager {
     pub fn init(&mut self, oracle: Address, stablecoin: Address, stcspr: Address) {
-        self.owner.set(self.env().caller());
+        let caller = self.env().caller();
+        self.owner.set(caller);
+        self.treasury.set(caller);
         self.oracle.set(oracle);
         self.stablecoin.set(stablecoin);
         self.stcspr_token.set(stcspr);
-        self.total_collateral.set(0);
-        self.total_debt.set(0);
+        self.total_collateral.set(U256::zero());
+        self.total_debt.set(U256::zero());
         self.trove_count.set(0);
-        self.base_rate.set(0);
-        self.last_redemption_time.set(0);
+        self.outstanding_fees.set(U256::zero());
+        self.redemption_rate.set(RedemptionRate::default());
     }
 
     /// Set stability pool address
+    /// 
+    /// Note: this contract must also be registered via `CasperUSD::add_minter`
+    /// before any trove can borrow.
     pub fn set_stability_pool(&mut self, pool: Address) {
         self.only_owner();
         self.stability_pool.set(pool);
     }
 
+    /// Set the address receiving the protocol's share of interest
+    pub fn set_treasury(&mut self, treasury: Address) {
+        self.only_owner();
+        self.treasury.set(treasury);
+    }
+
     // === TROVE OPERATIONS ===
 
     /// Open a new trove with user-set interest rate
-    pub fn open_trove(&mut self, collateral: u64, debt: u64, interest_rate: u64) {
+    /// 
+    /// `prev_hint`/`next_hint` are the expected neighbours in the sorted list
+    /// (see `find_insert_position`), stale hints just cost extra gas.
+    pub fn open_trove(
+        &mut self,
+        collateral: U256,
+        debt: U256,
+        interest_rate: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>
+    ) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(!is_active, "Trove already exists");
+        if self.get_trove(caller).active {
+            self.env().revert(Error::TroveAlreadyExists);
+        }
         
-        assert!(collateral > 0, "Collateral must be positive");
-        assert!(debt >= MIN_DEBT, "Debt below minimum");
-        assert!(interest_rate >= MIN_INTEREST_RATE, "Interest rate too low");
-        assert!(interest_rate <= MAX_INTEREST_RATE, "Interest rate too high");
+        if collateral.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+        if debt < U256::from(MIN_DEBT) {
+            self.env().revert(Error::DebtBelowMinimum);
+        }
+        if interest_rate < MIN_INTEREST_RATE {
+            self.env().revert(Error::InterestRateTooLow);
+        }
+        if interest_rate > MAX_INTEREST_RATE {
+            self.env().revert(Error::InterestRateTooHigh);
+        }
         
         // Check collateral ratio
-        let price = self.get_price();
-        let collateral_value = (collateral * price) / DECIMALS;
-        let ratio = (collateral_value * 100) / debt;
-        assert!(ratio >= MIN_COLLATERAL_RATIO, "Below minimum collateral ratio");
+        let price = self.get_borrow_price();
+        let ratio = compute_cr(collateral, debt, price);
+        if ratio < U256::from(MIN_COLLATERAL_RATIO) {
+            self.env().revert(Error::BelowMinCollateralRatio);
+        }
         
         // Apply borrowing fee
-        let fee = (debt * BORROWING_FEE) / DECIMALS;
+        let fee = dec_mul(debt, BORROWING_FEE, Rounding::Up)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
         let total_debt = debt + fee;
         
+        if self.check_recovery_mode(price) {
+            // Liquity's ICR >= CCR, over the debt actually recorded -
+            // the MCR check above leaves out the fee
+            if compute_cr(collateral, total_debt, price) < U256::from(CRITICAL_COLLATERAL_RATIO) {
+                self.env().revert(Error::BelowCriticalRatio);
+            }
+        } else {
+            let new_tcr = self.tcr_after(collateral, U256::zero(), total_debt, price);
+            if new_tcr < U256::from(CRITICAL_COLLATERAL_RATIO) {
+                self.env().revert(Error::WouldTriggerRecoveryMode);
+            }
+        }
+        
         // Store trove data
-        self.trove_collateral.set(&caller, collateral);
-        self.trove_debt.set(&caller, total_debt);
-        self.trove_interest_rate.set(&caller, interest_rate);
-        self.trove_last_update.set(&caller, self.env().get_block_time());
-        self.trove_active.set(&caller, true);
+        let stake = self.rewards.compute_stake(collateral);
+        self.store_trove(caller, Trove {
+            collateral,
+            debt: total_debt,
+            interest_rate,
+            last_update: self.env().get_block_time_secs(),
+            active: true,
+            zombie: false,
+            stake,
+            l_coll_snapshot: self.rewards.l_coll(),
+            l_debt_snapshot: self.rewards.l_debt(),
+        });
+        self.rewards.update_stake(U256::zero(), stake);
+        self.sorted_troves.insert(caller, interest_rate, prev_hint, next_hint);
         
         // Update totals
         let total_coll = self.total_collateral.get_or_default();
@@ -100,259 +304,1549 @@ impl TroveManager {
         self.total_debt.set(total_d + total_debt);
         let count = self.trove_count.get_or_default();
         self.trove_count.set(count + 1);
+        let fees = self.outstanding_fees.get_or_default();
+        self.outstanding_fees.set(fees + fee);
+        
+        // Pull collateral from borrower (requires prior approval)
+        self.pull_collateral(caller, collateral);
+        // Borrower receives the requested amount, the fee stays as debt only
+        self.cusd_ref().mint(caller, debt);
+        
+        self.env().emit_event(TroveOpened {
+            owner: caller,
+            collateral,
+            debt: total_debt,
+            interest_rate,
+            fee,
+        });
     }
 
-    /// Adjust interest rate
-    pub fn adjust_interest_rate(&mut self, new_rate: u64) {
+    /// Adjust interest rate, moving the trove to its new place in the sorted list
+    pub fn adjust_interest_rate(
+        &mut self,
+        new_rate: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>
+    ) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
-        assert!(new_rate >= MIN_INTEREST_RATE, "Rate too low");
-        assert!(new_rate <= MAX_INTEREST_RATE, "Rate too high");
+        if !self.get_trove(caller).active {
+            self.env().revert(Error::NoActiveTrove);
+        }
+        if new_rate < MIN_INTEREST_RATE {
+            self.env().revert(Error::InterestRateTooLow);
+        }
+        if new_rate > MAX_INTEREST_RATE {
+            self.env().revert(Error::InterestRateTooHigh);
+        }
         
         // Accrue interest before changing rate
-        self.accrue_interest_for(caller);
+        let mut trove = self.sync_trove(caller);
         
-        self.trove_interest_rate.set(&caller, new_rate);
+        trove.interest_rate = new_rate;
+        if !trove.zombie {
+            self.sorted_troves.re_insert(caller, new_rate, prev_hint, next_hint);
+        }
+        self.store_trove(caller, trove.clone());
+        self.emit_trove_adjusted(caller, &trove);
     }
 
     /// Add collateral
-    pub fn add_collateral(&mut self, amount: u64) {
+    pub fn add_collateral(&mut self, amount: U256) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
+        if !self.get_trove(caller).active {
+            self.env().revert(Error::NoActiveTrove);
+        }
         
-        self.accrue_interest_for(caller);
+        let mut trove = self.sync_trove(caller);
         
-        let current = self.trove_collateral.get(&caller).unwrap_or(0);
-        self.trove_collateral.set(&caller, current + amount);
+        trove.collateral += amount;
+        self.update_stake(&mut trove);
+        self.store_trove(caller, trove.clone());
+        self.emit_trove_adjusted(caller, &trove);
         
         let total = self.total_collateral.get_or_default();
         self.total_collateral.set(total + amount);
+        
+        self.pull_collateral(caller, amount);
     }
 
     /// Withdraw collateral
-    pub fn withdraw_collateral(&mut self, amount: u64) {
+    pub fn withdraw_collateral(&mut self, amount: U256) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
-        
-        let collateral = self.trove_collateral.get(&caller).unwrap_or(0);
-        assert!(collateral >= amount, "Insufficient collateral");
+        let trove = self.get_trove(caller);
+        if !trove.active {
+            self.env().revert(Error::NoActiveTrove);
+        }
+        if trove.collateral < amount {
+            self.env().revert(Error::InsufficientCollateral);
+        }
         
-        self.accrue_interest_for(caller);
+        let mut trove = self.sync_trove(caller);
         
-        let new_collateral = collateral - amount;
-        let debt = self.trove_debt.get(&caller).unwrap_or(0);
+        let new_collateral = trove.collateral - amount;
+        let debt = trove.debt;
+        let price = self.get_borrow_price();
         
-        if debt > 0 {
-            let price = self.get_price();
-            let collateral_value = (new_collateral * price) / DECIMALS;
-            let ratio = (collateral_value * 100) / debt;
-            assert!(ratio >= MIN_COLLATERAL_RATIO, "Would breach minimum ratio");
+        if !debt.is_zero() {
+            let ratio = compute_cr(new_collateral, debt, price);
+            if ratio < U256::from(MIN_COLLATERAL_RATIO) {
+                self.env().revert(Error::BelowMinCollateralRatio);
+            }
         }
+        self.check_collateral_removal(amount, price);
         
-        self.trove_collateral.set(&caller, new_collateral);
+        trove.collateral = new_collateral;
+        self.update_stake(&mut trove);
+        self.store_trove(caller, trove.clone());
+        self.emit_trove_adjusted(caller, &trove);
         
         let total = self.total_collateral.get_or_default();
         self.total_collateral.set(total - amount);
+        
+        self.push_collateral(caller, amount);
     }
 
     /// Borrow more cUSD
-    pub fn borrow(&mut self, amount: u64) {
+    /// 
+    /// The hints are only used when this revives a zombie trove, to put it
+    /// back in the sorted list - `None` for both is fine otherwise.
+    pub fn borrow(
+        &mut self,
+        amount: U256,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>
+    ) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
+        if !self.get_trove(caller).active {
+            self.env().revert(Error::NoActiveTrove);
+        }
         
-        self.accrue_interest_for(caller);
+        let mut trove = self.sync_trove(caller);
         
-        let fee = (amount * BORROWING_FEE) / DECIMALS;
-        let current_debt = self.trove_debt.get(&caller).unwrap_or(0);
-        let new_debt = current_debt + amount + fee;
+        let fee = dec_mul(amount, BORROWING_FEE, Rounding::Up)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let new_debt = trove.debt + amount + fee;
         
-        let collateral = self.trove_collateral.get(&caller).unwrap_or(0);
-        let price = self.get_price();
-        let collateral_value = (collateral * price) / DECIMALS;
-        let ratio = (collateral_value * 100) / new_debt;
-        assert!(ratio >= MIN_COLLATERAL_RATIO, "Would breach minimum ratio");
+        let collateral = trove.collateral;
+        let price = self.get_borrow_price();
+        let ratio = compute_cr(collateral, new_debt, price);
+        if ratio < U256::from(MIN_COLLATERAL_RATIO) {
+            self.env().revert(Error::BelowMinCollateralRatio);
+        }
+        
+        // Borrowing always lowers the trove's ratio, so it's off in recovery mode
+        if self.check_recovery_mode(price) {
+            self.env().revert(Error::NotAllowedInRecoveryMode);
+        }
+        let new_tcr = self.tcr_after(U256::zero(), U256::zero(), amount + fee, price);
+        if new_tcr < U256::from(CRITICAL_COLLATERAL_RATIO) {
+            self.env().revert(Error::WouldTriggerRecoveryMode);
+        }
         
-        self.trove_debt.set(&caller, new_debt);
+        trove.debt = new_debt;
+        // Borrowing back above the minimum revives a zombie trove
+        if trove.zombie && new_debt >= U256::from(MIN_DEBT) {
+            trove.zombie = false;
+            self.sorted_troves.insert(caller, trove.interest_rate, prev_hint, next_hint);
+        }
+        self.store_trove(caller, trove.clone());
+        self.emit_trove_adjusted(caller, &trove);
         
         let total = self.total_debt.get_or_default();
         self.total_debt.set(total + amount + fee);
+        let fees = self.outstanding_fees.get_or_default();
+        self.outstanding_fees.set(fees + fee);
+        
+        self.cusd_ref().mint(caller, amount);
     }
 
     /// Repay debt
-    pub fn repay(&mut self, amount: u64) {
+    pub fn repay(&mut self, amount: U256) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
+        if !self.get_trove(caller).active {
+            self.env().revert(Error::NoActiveTrove);
+        }
         
-        self.accrue_interest_for(caller);
+        let mut trove = self.sync_trove(caller);
         
-        let current_debt = self.trove_debt.get(&caller).unwrap_or(0);
-        let repay_amount = if amount > current_debt { current_debt } else { amount };
+        let repay_amount = if amount > trove.debt { trove.debt } else { amount };
         
-        self.trove_debt.set(&caller, current_debt - repay_amount);
+        trove.debt -= repay_amount;
+        self.store_trove(caller, trove.clone());
+        self.emit_trove_adjusted(caller, &trove);
         
         let total = self.total_debt.get_or_default();
         self.total_debt.set(total - repay_amount);
+        
+        self.cusd_ref().burn(caller, repay_amount);
     }
 
     /// Close trove
     pub fn close_trove(&mut self) {
         let caller = self.env().caller();
-        let is_active = self.trove_active.get(&caller).unwrap_or(false);
-        assert!(is_active, "No active trove");
-        
-        self.accrue_interest_for(caller);
+        if !self.get_trove(caller).active {
+            self.env().revert(Error::NoActiveTrove);
+        }
         
-        let debt = self.trove_debt.get(&caller).unwrap_or(0);
-        assert!(debt == 0, "Must repay all debt first");
+        let trove = self.sync_trove(caller);
+        if !trove.debt.is_zero() {
+            self.env().revert(Error::OutstandingDebt);
+        }
         
-        let collateral = self.trove_collateral.get(&caller).unwrap_or(0);
+        let collateral = trove.collateral;
+        let price = self.fetch_price();
+        self.check_collateral_removal(collateral, price);
         
-        self.trove_active.set(&caller, false);
-        self.trove_collateral.set(&caller, 0);
-        self.trove_debt.set(&caller, 0);
+        self.rewards.update_stake(trove.stake, U256::zero());
+        if !trove.zombie {
+            self.sorted_troves.remove(caller);
+        }
+        self.store_trove(caller, Trove::default());
         
         let total = self.total_collateral.get_or_default();
         self.total_collateral.set(total - collateral);
         
         let count = self.trove_count.get_or_default();
         self.trove_count.set(count - 1);
+        
+        self.push_collateral(caller, collateral);
+        
+        self.env().emit_event(TroveClosed { owner: caller, collateral });
+    }
+
+    /// Collateral may only leave the system outside recovery mode, and not
+    /// push it into recovery mode - debt-free troves included, since
+    /// pulling out collateral that backs nothing still lowers the TCR
+    fn check_collateral_removal(&self, amount: U256, price: U256) {
+        if self.check_recovery_mode(price) {
+            self.env().revert(Error::NotAllowedInRecoveryMode);
+        }
+        let new_tcr = self.tcr_after(U256::zero(), amount, U256::zero(), price);
+        if new_tcr < U256::from(CRITICAL_COLLATERAL_RATIO) {
+            self.env().revert(Error::WouldTriggerRecoveryMode);
+        }
     }
 
     // === LIQUIDATION ===
 
     /// Check if trove is liquidatable
+    /// 
+    /// In recovery mode this also covers troves below the critical ratio
+    /// that are weaker than the system as a whole.
     pub fn is_liquidatable(&self, owner: Address) -> bool {
-        let is_active = self.trove_active.get(&owner).unwrap_or(false);
-        if !is_active { return false; }
+        let trove = self.get_trove(owner);
+        self.can_liquidate(&trove, self.get_price())
+    }
+
+    /// Liquidate undercollateralized trove
+    /// 
+    /// The Stability Pool absorbs the debt and receives the collateral, which
+    /// is worth more than the debt it cancels - that surplus is the depositors'
+    /// liquidation bonus. Whatever the pool can't cover is redistributed to
+    /// all other active troves. The caller gets a small slice of collateral
+    /// as gas compensation.
+    /// 
+    /// Recovery mode liquidations of troves above the liquidation ratio
+    /// take at most 110% of the debt in collateral and need the pool to
+    /// cover the whole debt; the owner gets the rest back.
+    pub fn liquidate(&mut self, owner: Address) {
+        let price = self.fetch_price();
+        let pool_deposits = self.pool_ref().get_total_deposits();
+        let mut totals = LiquidationTotals::default();
+        if let Err(error) = self.liquidate_trove(owner, price, pool_deposits, &mut totals) {
+            self.env().revert(error);
+        }
+        self.settle_liquidations(&totals, price);
+    }
+
+    /// Liquidate every liquidatable trove in `owners`
+    /// 
+    /// Entries that can't be liquidated are skipped, and the pool offset is
+    /// settled in a single call. Reverts if nothing was liquidated.
+    pub fn batch_liquidate(&mut self, owners: Vec<Address>) -> LiquidationTotals {
+        let price = self.fetch_price();
+        let pool_deposits = self.pool_ref().get_total_deposits();
+        let mut totals = LiquidationTotals::default();
+        for owner in owners {
+            let _ = self.liquidate_trove(owner, price, pool_deposits, &mut totals);
+        }
+        if totals.troves == 0 {
+            self.env().revert(Error::NotLiquidatable);
+        }
+        self.settle_liquidations(&totals, price);
+        totals
+    }
+
+    /// Liquidate up to `n` of the riskiest troves in the sorted list
+    /// 
+    /// The list is ordered by interest rate, not by risk, so every trove in
+    /// it is read and the liquidatable ones are ranked by ICR, lowest first;
+    /// `n` bounds the liquidations. Zombie troves aren't in the list - keepers
+    /// reach them through `batch_liquidate`.
+    pub fn liquidate_troves(&mut self, n: u32) -> LiquidationTotals {
+        let price = self.fetch_price();
+        let mut candidates = Vec::new();
+        let mut next = self.sorted_troves.first();
+        while let Some(owner) = next {
+            let trove = self.get_trove(owner);
+            if self.can_liquidate(&trove, price) {
+                candidates.push((compute_cr(trove.collateral, trove.debt, price), owner));
+            }
+            next = self.sorted_troves.next(owner);
+        }
+        candidates.sort_by_key(|(ratio, _)| *ratio);
         
-        let debt = self.trove_debt.get(&owner).unwrap_or(0);
-        if debt == 0 { return false; }
+        let pool_deposits = self.pool_ref().get_total_deposits();
+        let mut totals = LiquidationTotals::default();
+        for (_, owner) in candidates {
+            if totals.troves >= n as u64 { break; }
+            let _ = self.liquidate_trove(owner, price, pool_deposits, &mut totals);
+        }
+        if totals.troves == 0 {
+            self.env().revert(Error::NotLiquidatable);
+        }
+        self.settle_liquidations(&totals, price);
+        totals
+    }
+
+    /// Liquidation check at a given price
+    fn can_liquidate(&self, trove: &Trove, price: U256) -> bool {
+        if !trove.active { return false; }
+        if trove.debt.is_zero() { return false; }
         
-        let collateral = self.trove_collateral.get(&owner).unwrap_or(0);
-        let price = self.get_price();
-        let collateral_value = (collateral * price) / DECIMALS;
-        let ratio = (collateral_value * 100) / debt;
+        let ratio = compute_cr(trove.collateral, trove.debt, price);
+        if ratio < U256::from(LIQUIDATION_RATIO) { return true; }
         
-        ratio < LIQUIDATION_RATIO
+        self.check_recovery_mode(price)
+            && ratio < U256::from(CRITICAL_COLLATERAL_RATIO)
+            && ratio < self.system_cr(price)
     }
 
-    /// Liquidate undercollateralized trove
-    pub fn liquidate(&mut self, owner: Address) {
-        assert!(self.is_liquidatable(owner), "Trove not liquidatable");
+    /// Close out one trove and add it to `totals`
+    /// 
+    /// Redistribution, the owner's surplus and the system totals are applied
+    /// right away, so later troves in a batch see the updated recovery mode.
+    /// The pool offset and gas compensation are left to `settle_liquidations`.
+    fn liquidate_trove(
+        &mut self,
+        owner: Address,
+        price: U256,
+        pool_deposits: U256,
+        totals: &mut LiquidationTotals
+    ) -> Result<(), Error> {
+        if !self.can_liquidate(&self.get_trove(owner), price) {
+            return Err(Error::NotLiquidatable);
+        }
+        // Deposits not yet used up by earlier troves in this batch
+        let pool_remaining = pool_deposits - totals.debt_offset;
+        
+        let trove = self.sync_trove(owner);
+        let debt = trove.debt;
+        let mut collateral = trove.collateral;
+        
+        let mut surplus = U256::zero();
+        if compute_cr(collateral, debt, price) >= U256::from(LIQUIDATION_RATIO) {
+            if pool_remaining < debt {
+                return Err(Error::StabilityPoolTooSmall);
+            }
+            let capped_value = percent_of(debt, LIQUIDATION_RATIO, Rounding::Up)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            let capped = dec_div(capped_value, price, Rounding::Up)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            surplus = collateral.saturating_sub(capped);
+            collateral -= surplus;
+        }
+        
+        let gas_compensation = collateral / COLL_GAS_COMPENSATION_DIVISOR;
+        let collateral_to_liquidate = collateral - gas_compensation;
         
-        let debt = self.trove_debt.get(&owner).unwrap_or(0);
-        let collateral = self.trove_collateral.get(&owner).unwrap_or(0);
+        // Stability Pool takes as much as its deposits allow, the rest is redistributed
+        let debt_to_offset = if debt < pool_remaining { debt } else { pool_remaining };
+        let coll_to_offset = mul_div(collateral_to_liquidate, debt_to_offset, debt, Rounding::Down)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        let debt_to_redistribute = debt - debt_to_offset;
+        let coll_to_redistribute = collateral_to_liquidate - coll_to_offset;
         
-        // 5% liquidation penalty
-        let penalty = (collateral * 5) / 100;
-        let _collateral_to_pool = collateral - penalty;
+        // Redistribution needs another trove's stake to land on
+        if !debt_to_redistribute.is_zero() && self.rewards.total_stakes() == trove.stake {
+            return Err(Error::OnlyOneTrove);
+        }
         
         // Clear trove
-        self.trove_active.set(&owner, false);
-        self.trove_debt.set(&owner, 0);
-        self.trove_collateral.set(&owner, 0);
+        self.rewards.update_stake(trove.stake, U256::zero());
+        if !trove.zombie {
+            self.sorted_troves.remove(owner);
+        }
+        self.store_trove(owner, Trove::default());
         
-        // Update totals
+        // Update totals - redistributed amounts stay with the remaining troves
         let total_coll = self.total_collateral.get_or_default();
-        self.total_collateral.set(total_coll - collateral);
+        self.total_collateral.set(total_coll - (collateral + surplus - coll_to_redistribute));
         let total_d = self.total_debt.get_or_default();
-        self.total_debt.set(total_d - debt);
+        self.total_debt.set(total_d - debt_to_offset);
         let count = self.trove_count.get_or_default();
         self.trove_count.set(count - 1);
+        
+        if !debt_to_redistribute.is_zero() {
+            self.rewards.redistribute(coll_to_redistribute, debt_to_redistribute);
+        }
+        self.rewards.update_snapshots(self.total_collateral.get_or_default());
+        self.push_collateral(owner, surplus);
+        
+        totals.troves += 1;
+        totals.debt += debt;
+        totals.collateral += collateral;
+        totals.debt_offset += debt_to_offset;
+        totals.collateral_offset += coll_to_offset;
+        totals.debt_redistributed += debt_to_redistribute;
+        totals.collateral_redistributed += coll_to_redistribute;
+        totals.gas_compensation += gas_compensation;
+        
+        self.env().emit_event(TroveLiquidated {
+            owner,
+            liquidator: self.env().caller(),
+            debt,
+            collateral,
+            debt_offset: debt_to_offset,
+            debt_redistributed: debt_to_redistribute,
+            collateral_surplus: surplus,
+        });
+        Ok(())
+    }
+
+    /// Cancel the batch's debt against pool deposits, hand over the
+    /// collateral and pay the caller's gas compensation
+    fn settle_liquidations(&mut self, totals: &LiquidationTotals, price: U256) {
+        if !totals.debt_offset.is_zero() {
+            let pool_address = self.stability_pool.get_or_revert_with(Error::StabilityPoolNotSet);
+            self.cusd_ref().burn(pool_address, totals.debt_offset);
+            self.push_collateral(pool_address, totals.collateral_offset);
+            self.pool_ref().offset(totals.debt_offset, totals.collateral_offset, price);
+        }
+        
+        let liquidator = self.env().caller();
+        self.push_collateral(liquidator, totals.gas_compensation);
     }
 
     // === REDEMPTION ===
 
+    /// Redeem cUSD for stCSPR at face value, minus the redemption fee
+    /// 
+    /// Walks troves from the lowest interest rate upwards, cancelling their
+    /// debt against the redeemed cUSD. The fee is left in the redeemed trove
+    /// as extra collateral. Troves left below minimum debt become zombies.
+    /// 
+    /// The base rate and fee are set by the debt actually redeemed, which
+    /// falls short of `cusd_amount` when the list runs out or after
+    /// `max_iterations` troves (0 for no limit).
+    pub fn redeem_collateral(&mut self, cusd_amount: U256, max_fee: u64, max_iterations: u32) {
+        let redeemer = self.env().caller();
+        if cusd_amount.is_zero() {
+            self.env().revert(Error::ZeroAmount);
+        }
+        
+        let price = self.fetch_price();
+        let supply = self.cusd_ref().total_supply();
+        
+        // Pick the troves and amounts first - the fee depends on the total
+        let mut redemptions: Vec<(Address, U256)> = Vec::new();
+        let mut remaining = cusd_amount;
+        let mut total_debt_redeemed = U256::zero();
+        let mut current = self.sorted_troves.first();
+        let mut iterations = 0u32;
+        
+        while let Some(owner) = current {
+            if remaining.is_zero() { break; }
+            if max_iterations > 0 && iterations == max_iterations { break; }
+            iterations += 1;
+            current = self.sorted_troves.next(owner);
+            
+            // Undercollateralized troves are for liquidators, not redeemers
+            if self.can_liquidate(&self.get_trove(owner), price) { continue; }
+            
+            let trove = self.sync_trove(owner);
+            if trove.debt.is_zero() { continue; }
+            
+            let debt_redeemed = if remaining < trove.debt { remaining } else { trove.debt };
+            redemptions.push((owner, debt_redeemed));
+            remaining -= debt_redeemed;
+            total_debt_redeemed += debt_redeemed;
+        }
+        
+        if total_debt_redeemed.is_zero() {
+            self.env().revert(Error::NothingToRedeem);
+        }
+        
+        self.update_base_rate_from_redemption(total_debt_redeemed, supply);
+        let fee_rate = self.get_redemption_fee();
+        if fee_rate > max_fee {
+            self.env().revert(Error::FeeExceedsMaximum);
+        }
+        
+        let mut total_coll_drawn = U256::zero();
+        for (owner, debt_redeemed) in redemptions {
+            let mut trove = self.troves.get(&owner).unwrap_or_default();
+            let coll_redeemed = dec_div(debt_redeemed, price, Rounding::Down)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            let coll_fee = dec_mul(coll_redeemed, fee_rate, Rounding::Up)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+            let coll_drawn = coll_redeemed - coll_fee;
+            
+            trove.debt -= debt_redeemed;
+            trove.collateral -= coll_drawn;
+            self.update_stake(&mut trove);
+            
+            if trove.debt < U256::from(MIN_DEBT) {
+                trove.zombie = true;
+                self.sorted_troves.remove(owner);
+            }
+            self.store_trove(owner, trove.clone());
+            self.emit_trove_adjusted(owner, &trove);
+            
+            total_coll_drawn += coll_drawn;
+        }
+        
+        let total_coll = self.total_collateral.get_or_default();
+        self.total_collateral.set(total_coll - total_coll_drawn);
+        let total_d = self.total_debt.get_or_default();
+        self.total_debt.set(total_d - total_debt_redeemed);
+        
+        self.cusd_ref().burn(redeemer, total_debt_redeemed);
+        self.push_collateral(redeemer, total_coll_drawn);
+        
+        self.env().emit_event(Redemption {
+            redeemer,
+            cusd_amount,
+            debt_redeemed: total_debt_redeemed,
+            collateral_drawn: total_coll_drawn,
+            fee_rate,
+        });
+    }
+
     /// Get current redemption fee rate
     pub fn get_redemption_fee(&self) -> u64 {
-        let base = self.base_rate.get_or_default();
+        let base = self.calc_decayed_base_rate();
         if base > REDEMPTION_FEE_FLOOR { base } else { REDEMPTION_FEE_FLOOR }
     }
 
-    // === INTEREST ACCRUAL ===
+    /// Decay base rate, then bump it by the redeemed share of supply
+    fn update_base_rate_from_redemption(&mut self, cusd_amount: U256, supply: U256) {
+        let decayed = self.calc_decayed_base_rate();
+        // At most the whole supply, so the fraction fits in a decimal
+        let redeemed_fraction = if cusd_amount >= supply {
+            DECIMALS
+        } else {
+            Decimal::from_ratio(cusd_amount, supply, Rounding::Up)
+                .unwrap_or_revert_with(self, Error::MathOverflow)
+                .raw_u64()
+        };
+        let new_base = decayed + redeemed_fraction / REDEMPTION_BETA;
+        
+        // Only move the clock by whole minutes so partial decay isn't lost
+        let minutes = self.minutes_since_last_redemption();
+        let last = self.redemption_rate.get_or_default().last_redemption_time;
+        self.redemption_rate.set(RedemptionRate {
+            base_rate: if new_base > DECIMALS { DECIMALS } else { new_base },
+            last_redemption_time: last + minutes * 60,
+        });
+    }
 
-    fn accrue_interest_for(&mut self, user: Address) {
-        let now = self.env().get_block_time();
-        let last_update = self.trove_last_update.get(&user).unwrap_or(now);
-        let elapsed = now - last_update;
+    fn calc_decayed_base_rate(&self) -> u64 {
+        let minutes = self.minutes_since_last_redemption();
+        let decay = dec_pow(Decimal::from_raw(MINUTE_DECAY_FACTOR), minutes)
+            .unwrap_or_revert_with(self, Error::MathOverflow);
+        Decimal::from_raw(self.redemption_rate.get_or_default().base_rate)
+            .mul(decay, Rounding::Up)
+            .unwrap_or_revert_with(self, Error::MathOverflow)
+            .raw_u64()
+    }
+
+    fn minutes_since_last_redemption(&self) -> u64 {
+        let now = self.env().get_block_time_secs();
+        (now - self.redemption_rate.get_or_default().last_redemption_time) / 60
+    }
+
+    // === INTEREST ACCRUAL & REWARDS ===
+
+    /// Mint the interest accrued by all troves since the last trove update
+    /// 
+    /// Every trove operation does this already; anyone may call it to pay
+    /// out depositors' yield in between.
+    pub fn mint_interest(&mut self) {
+        self.accrue_interest();
+    }
+
+    /// Accrues interest and applies pending redistribution rewards,
+    /// stores and returns the updated trove
+    fn sync_trove(&mut self, user: Address) -> Trove {
+        let mut trove = self.troves.get(&user).unwrap_or_default();
+        let now = self.env().get_block_time_secs();
+        let elapsed = now - trove.last_update;
         
-        if elapsed > 0 {
-            let debt = self.trove_debt.get(&user).unwrap_or(0);
-            let rate = self.trove_interest_rate.get(&user).unwrap_or(0);
-            
-            if debt > 0 && rate > 0 {
-                let interest = (debt * rate * elapsed) / (DECIMALS * SECONDS_PER_YEAR);
-                self.trove_debt.set(&user, debt + interest);
-                
-                let total = self.total_debt.get_or_default();
-                self.total_debt.set(total + interest);
-            }
+        // Already counted in the aggregate, only the trove's own debt grows
+        if elapsed > 0 && !trove.debt.is_zero() {
+            trove.debt += trove_interest(trove.debt, trove.interest_rate, elapsed)
+                .unwrap_or_revert_with(self, Error::MathOverflow);
+        }
+        
+        trove.last_update = now;
+        
+        // Redistributed amounts are already in the totals, only the trove changes
+        let (pending_coll, pending_debt) = self.pending_rewards(&trove);
+        trove.collateral += pending_coll;
+        trove.debt += pending_debt;
+        trove.l_coll_snapshot = self.rewards.l_coll();
+        trove.l_debt_snapshot = self.rewards.l_debt();
+        if trove.active {
+            self.update_stake(&mut trove);
+        }
+        
+        self.store_trove(user, trove.clone());
+        trove
+    }
+
+    /// Store a trove, moving its weight in the aggregate interest sum
+    /// 
+    /// Pending aggregate interest is folded in first, so the old weight
+    /// is charged right up to now.
+    fn store_trove(&mut self, owner: Address, trove: Trove) {
+        self.accrue_interest();
+        let old = self.troves.get(&owner).unwrap_or_default();
+        self.interest.update_weight(old.debt, old.interest_rate, trove.debt, trove.interest_rate);
+        self.troves.set(&owner, trove);
+    }
+
+    /// Add the interest accrued by all troves to the recorded debt and mint it
+    /// 
+    /// Depositors get `SP_YIELD_SPLIT` percent through the Stability Pool,
+    /// the treasury the rest - or all of it while the pool is empty. Before
+    /// a pool is set the interest stays unminted, as outstanding fees.
+    fn accrue_interest(&mut self) {
+        let now = self.env().get_block_time_secs();
+        let interest = self.interest.accrue(now);
+        if interest.is_zero() { return; }
+        
+        let total = self.total_debt.get_or_default();
+        self.total_debt.set(total + interest);
+        
+        let Some(pool_address) = self.stability_pool.get() else {
+            let fees = self.outstanding_fees.get_or_default();
+            self.outstanding_fees.set(fees + interest);
+            return;
+        };
+        let mut pool = self.pool_ref();
+        let to_pool = if pool.get_total_deposits().is_zero() {
+            U256::zero()
+        } else {
+            percent_of(interest, SP_YIELD_SPLIT, Rounding::Down)
+                .unwrap_or_revert_with(self, Error::MathOverflow)
+        };
+        let to_treasury = interest - to_pool;
+        
+        if !to_pool.is_zero() {
+            self.cusd_ref().mint(pool_address, to_pool);
+            pool.receive_interest(to_pool);
+        }
+        if !to_treasury.is_zero() {
+            self.cusd_ref().mint(self.treasury.get().unwrap(), to_treasury);
         }
         
-        self.trove_last_update.set(&user, now);
+        self.env().emit_event(InterestMinted { stability_pool: to_pool, treasury: to_treasury });
+    }
+
+    /// Recorded debt plus the interest accrued since it was minted
+    fn aggregate_debt(&self) -> U256 {
+        let pending = self.interest.pending_interest(self.env().get_block_time_secs());
+        self.total_debt.get_or_default() + pending
+    }
+
+    fn pending_rewards(&self, trove: &Trove) -> (U256, U256) {
+        if !trove.active { return (U256::zero(), U256::zero()); }
+        self.rewards.pending_rewards(trove.stake, trove.l_coll_snapshot, trove.l_debt_snapshot)
+    }
+
+    /// Stake tracks collateral so redistributions are pro-rata
+    fn update_stake(&mut self, trove: &mut Trove) {
+        let stake = self.rewards.compute_stake(trove.collateral);
+        self.rewards.update_stake(trove.stake, stake);
+        trove.stake = stake;
+    }
+
+    fn emit_trove_adjusted(&self, owner: Address, trove: &Trove) {
+        self.env().emit_event(TroveAdjusted {
+            owner,
+            collateral: trove.collateral,
+            debt: trove.debt,
+            interest_rate: trove.interest_rate,
+        });
     }
 
     // === VIEW FUNCTIONS ===
 
-    pub fn get_trove_collateral(&self, owner: Address) -> u64 {
-        self.trove_collateral.get(&owner).unwrap_or(0)
+    /// Trove including pending redistribution rewards
+    pub fn get_trove(&self, owner: Address) -> Trove {
+        let mut trove = self.troves.get(&owner).unwrap_or_default();
+        let (pending_coll, pending_debt) = self.pending_rewards(&trove);
+        trove.collateral += pending_coll;
+        trove.debt += pending_debt;
+        trove
     }
 
-    pub fn get_trove_debt(&self, owner: Address) -> u64 {
-        self.trove_debt.get(&owner).unwrap_or(0)
+    /// Redistribution rewards not yet applied to the trove: (collateral, debt)
+    pub fn get_pending_rewards(&self, owner: Address) -> (U256, U256) {
+        let trove = self.troves.get(&owner).unwrap_or_default();
+        self.pending_rewards(&trove)
+    }
+
+    pub fn get_trove_collateral(&self, owner: Address) -> U256 {
+        self.get_trove(owner).collateral
+    }
+
+    pub fn get_trove_debt(&self, owner: Address) -> U256 {
+        self.get_trove(owner).debt
     }
 
     pub fn get_trove_interest_rate(&self, owner: Address) -> u64 {
-        self.trove_interest_rate.get(&owner).unwrap_or(0)
+        self.get_trove(owner).interest_rate
     }
 
     pub fn get_trove_active(&self, owner: Address) -> bool {
-        self.trove_active.get(&owner).unwrap_or(false)
+        self.get_trove(owner).active
     }
 
-    pub fn get_collateral_ratio(&self, owner: Address) -> u64 {
-        let debt = self.trove_debt.get(&owner).unwrap_or(0);
-        if debt == 0 { return 0; }
+    pub fn get_collateral_ratio(&self, owner: Address) -> U256 {
+        let trove = self.get_trove(owner);
+        if trove.debt.is_zero() { return U256::zero(); }
         
-        let collateral = self.trove_collateral.get(&owner).unwrap_or(0);
         let price = self.get_price();
-        let collateral_value = (collateral * price) / DECIMALS;
-        (collateral_value * 100) / debt
+        compute_cr(trove.collateral, trove.debt, price)
     }
 
-    pub fn get_total_collateral(&self) -> u64 {
+    pub fn get_total_collateral(&self) -> U256 {
         self.total_collateral.get_or_default()
     }
 
-    pub fn get_total_debt(&self) -> u64 {
-        self.total_debt.get_or_default()
+    /// stCSPR actually held by this contract - should equal total collateral
+    pub fn get_collateral_token_balance(&self) -> U256 {
+        self.stcspr_ref().balance_of(self.env().self_address())
+    }
+
+    pub fn get_treasury(&self) -> Address {
+        self.treasury.get().unwrap()
+    }
+
+    /// Total debt, interest accrued since the last trove update included
+    pub fn get_total_debt(&self) -> U256 {
+        self.aggregate_debt()
+    }
+
+    /// Fees and interest owed by troves that haven't been minted as cUSD
+    pub fn get_outstanding_fees(&self) -> U256 {
+        let pending = self.interest.pending_interest(self.env().get_block_time_secs());
+        self.outstanding_fees.get_or_default() + pending
+    }
+
+    /// Supply invariant: cUSD supply + outstanding fees == total debt
+    pub fn is_supply_reconciled(&self) -> bool {
+        let supply = self.cusd_ref().total_supply();
+        supply + self.outstanding_fees.get_or_default() == self.total_debt.get_or_default()
     }
 
     pub fn get_trove_count(&self) -> u64 {
         self.trove_count.get_or_default()
     }
 
+    /// Recovery mode: system collateral ratio below the critical ratio
+    pub fn is_recovery_mode(&self) -> bool {
+        self.check_recovery_mode(self.get_price())
+    }
+
+    // === SORTED TROVES ===
+
+    /// Lowest-rate trove - next in line for redemption
+    pub fn get_first_trove(&self) -> Option<Address> {
+        self.sorted_troves.first()
+    }
+
+    /// Highest-rate trove
+    pub fn get_last_trove(&self) -> Option<Address> {
+        self.sorted_troves.last()
+    }
+
+    pub fn get_next_trove(&self, owner: Address) -> Option<Address> {
+        self.sorted_troves.next(owner)
+    }
+
+    pub fn get_prev_trove(&self, owner: Address) -> Option<Address> {
+        self.sorted_troves.prev(owner)
+    }
+
+    /// Troves in redemption order, `count` at a time from `start` (or the first)
+    pub fn get_troves(&self, start: Option<Address>, count: u32) -> Vec<Address> {
+        self.sorted_troves.page(start, count)
+    }
+
+    /// Number of troves in the sorted list (zombies excluded)
+    pub fn get_sorted_troves_size(&self) -> u64 {
+        self.sorted_troves.size()
+    }
+
+    /// Exact `(prev, next)` neighbours for a trove with `interest_rate`,
+    /// to be passed as hints to `open_trove`/`adjust_interest_rate`/`borrow`
+    pub fn find_insert_position(
+        &self,
+        interest_rate: u64,
+        prev_hint: Option<Address>,
+        next_hint: Option<Address>
+    ) -> (Option<Address>, Option<Address>) {
+        self.sorted_troves.find_insert_position(interest_rate, prev_hint, next_hint)
+    }
+
     /// Total Collateral Ratio
-    pub fn get_tcr(&self) -> u64 {
-        let total_debt = self.total_debt.get_or_default();
-        if total_debt == 0 { return 0; }
-        
-        let price = self.get_price();
-        let total_coll_value = (self.total_collateral.get_or_default() * price) / DECIMALS;
-        (total_coll_value * 100) / total_debt
+    pub fn get_tcr(&self) -> U256 {
+        if self.aggregate_debt().is_zero() { return U256::zero(); }
+        self.system_cr(self.get_price())
+    }
+
+    fn check_recovery_mode(&self, price: U256) -> bool {
+        if self.aggregate_debt().is_zero() { return false; }
+        self.system_cr(price) < U256::from(CRITICAL_COLLATERAL_RATIO)
+    }
+
+    fn system_cr(&self, price: U256) -> U256 {
+        compute_cr(self.total_collateral.get_or_default(), self.aggregate_debt(), price)
+    }
+
+    /// System collateral ratio after a pending change
+    fn tcr_after(&self, coll_added: U256, coll_removed: U256, debt_added: U256, price: U256) -> U256 {
+        let collateral = self.total_collateral.get_or_default() + coll_added - coll_removed;
+        let debt = self.aggregate_debt() + debt_added;
+        compute_cr(collateral, debt, price)
+    }
+
+    // === PRICE FEED ===
+
+    /// Spot price from the router's trusted source - used for liquidations and redemptions
+    ///
+    /// Reverts when neither oracle can be trusted rather than act on the last good price.
+    fn fetch_price(&mut self) -> U256 {
+        let mut oracle = self.oracle_ref();
+        let price = oracle.fetch_price();
+        if let PriceSource::LastGood = oracle.get_active_source() {
+            self.env().revert(Error::PriceUnavailable);
+        }
+        price
+    }
+
+    /// The same spot price for views, read without stepping the router
+    fn get_price(&self) -> U256 {
+        match self.oracle_ref().peek_price() {
+            Some(price) => price,
+            None => self.env().revert(Error::PriceUnavailable),
+        }
+    }
+
+    /// Conservative price for operations that add risk:
+    /// the lower of spot and TWAP, so a short spike can't be borrowed against
+    fn get_borrow_price(&mut self) -> U256 {
+        let spot = self.fetch_price();
+        let twap = self.oracle_ref().get_twap_price();
+        if spot < twap { spot } else { twap }
     }
 
-    fn get_price(&self) -> u64 {
-        // TODO: Call oracle contract
-        50_000_000 // $0.05 default
+    fn oracle_ref(&self) -> OracleRouterContractRef {
+        OracleRouterContractRef::new(self.env(), self.oracle.get().unwrap())
+    }
+
+    // === TOKEN TRANSFERS ===
+
+    fn pull_collateral(&mut self, from: Address, amount: U256) {
+        if amount.is_zero() { return; }
+        let this = self.env().self_address();
+        self.stcspr_ref().transfer_from(from, this, amount);
+    }
+
+    fn push_collateral(&mut self, to: Address, amount: U256) {
+        if amount.is_zero() { return; }
+        self.stcspr_ref().transfer(to, amount);
+    }
+
+    fn cusd_ref(&self) -> CasperUSDContractRef {
+        CasperUSDContractRef::new(self.env(), self.stablecoin.get().unwrap())
+    }
+
+    fn stcspr_ref(&self) -> Cep18TokenContractRef {
+        Cep18TokenContractRef::new(self.env(), self.stcspr_token.get().unwrap())
+    }
+
+    fn pool_ref(&self) -> StabilityPoolContractRef {
+        StabilityPoolContractRef::new(self.env(), self.stability_pool.get_or_revert_with(Error::StabilityPoolNotSet))
     }
 
     fn only_owner(&self) {
-        assert!(self.env().caller() == self.owner.get().unwrap(), "Only owner");
+        if self.env().caller() != self.owner.get().unwrap() {
+            self.env().revert(Error::NotOwner);
+        }
+    }
+}
+
+/// Collateral ratio in percent, unbounded when there's no debt
+fn compute_cr(collateral: U256, debt: U256, price: U256) -> U256 {
+    // Collateral worth more than a U256 is as good as unbounded
+    dec_mul(collateral, price, Rounding::Down).map_or(U256::MAX, |value| ratio_percent(value, debt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregate_interest::SECONDS_PER_YEAR;
+    use crate::mock_stcspr::{MockStCSPR, MockStCSPRHostRef};
+    use crate::oracle::{PriceOracle, PriceOracleHostRef};
+    use crate::oracle_router::{OracleRouter, OracleRouterInitArgs};
+    use crate::stability_pool::{StabilityPool, StabilityPoolHostRef, StabilityPoolInitArgs};
+    use crate::stablecoin::{CasperUSD, CasperUSDHostRef};
+    use odra::host::{Deployer, HostEnv, NoArgs};
+    use odra::prelude::Addressable;
+
+    const CSPR: u64 = 1_000_000_000;
+    const FAUCET_CALLS: usize = 100; // 1,000,000 stCSPR
+
+    struct System {
+        env: HostEnv,
+        oracle: PriceOracleHostRef,
+        secondary: PriceOracleHostRef,
+        cusd: CasperUSDHostRef,
+        stcspr: MockStCSPRHostRef,
+        tm: TroveManagerHostRef,
+        pool: StabilityPoolHostRef,
+    }
+
+    fn setup() -> System {
+        let env = odra_test::env();
+        let oracle = PriceOracle::deploy(&env, NoArgs);
+        let secondary = PriceOracle::deploy(&env, NoArgs);
+        let mut cusd = CasperUSD::deploy(&env, NoArgs);
+        let stcspr = MockStCSPR::deploy(&env, NoArgs);
+        let router = OracleRouter::deploy(&env, OracleRouterInitArgs {
+            primary: oracle.address(),
+            secondary: secondary.address(),
+            lst: stcspr.address(),
+        });
+        let mut tm = TroveManager::deploy(&env, TroveManagerInitArgs {
+            oracle: router.address(),
+            stablecoin: cusd.address(),
+            stcspr: stcspr.address(),
+        });
+        let pool = StabilityPool::deploy(&env, StabilityPoolInitArgs {
+            stablecoin: cusd.address(),
+            stcspr: stcspr.address(),
+            trove_manager: tm.address(),
+        });
+        tm.set_stability_pool(pool.address());
+        cusd.add_minter(tm.address());
+        System { env, oracle, secondary, cusd, stcspr, tm, pool }
+    }
+
+    /// Open a trove backed by a million stCSPR
+    fn open_large_trove(sys: &mut System, account: usize, debt: U256, rate: u64) -> Address {
+        let owner = sys.env.get_account(account);
+        sys.env.set_caller(owner);
+        for _ in 0..FAUCET_CALLS {
+            sys.stcspr.faucet();
+        }
+        let collateral = sys.stcspr.balance_of(owner);
+        sys.stcspr.approve(sys.tm.address(), collateral);
+        sys.tm.open_trove(collateral, debt, rate, None, None);
+        owner
+    }
+
+    /// Settle the next round at `price` (the deployer is the only feeder)
+    fn feed(env: &HostEnv, oracle: &mut PriceOracleHostRef, price: U256) {
+        env.set_caller(env.get_account(0));
+        let round = oracle.get_open_round();
+        oracle.submit_price(round, price);
+    }
+
+    /// Feed the same price to both oracles behind the router
+    fn feed_price(sys: &mut System, price: U256) {
+        feed(&sys.env, &mut sys.oracle, price);
+        feed(&sys.env, &mut sys.secondary, price);
+    }
+
+    /// Walk the oracles to `target` in steps inside the circuit breaker's limit
+    fn move_price(sys: &mut System, target: U256) {
+        while sys.oracle.get_price() != target {
+            let price = sys.oracle.get_price();
+            let floor = price * 96u64 / 100u64;
+            let ceil = price * 104u64 / 100u64;
+            let step = if target < floor { floor } else if target > ceil { ceil } else { target };
+            feed_price(sys, step);
+        }
+    }
+
+    #[test]
+    fn collateral_ratios_follow_the_oracle_price() {
+        let mut sys = setup();
+        let owner = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), MIN_INTEREST_RATE);
+        // $50,000 against 20,100 cUSD at $0.05
+        assert_eq!(sys.tm.get_collateral_ratio(owner), U256::from(248));
+        assert!(!sys.tm.is_liquidatable(owner));
+
+        // At $0.022 the same collateral is worth $22,000
+        move_price(&mut sys, U256::from(22_000_000));
+        assert_eq!(sys.tm.get_collateral_ratio(owner), U256::from(109));
+        assert_eq!(sys.tm.get_tcr(), U256::from(109));
+        assert!(sys.tm.is_liquidatable(owner));
+    }
+
+    #[test]
+    fn collateral_moves_in_and_out_with_the_trove() {
+        let mut sys = setup();
+        let other = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let held = sys.tm.get_collateral_token_balance();
+        let owner = sys.env.get_account(2);
+        sys.env.set_caller(owner);
+        sys.stcspr.faucet();
+        sys.stcspr.approve(sys.tm.address(), U256::from(10_000 * CSPR));
+
+        sys.tm.open_trove(U256::from(6_000 * CSPR), U256::from(100 * CSPR), MIN_INTEREST_RATE, None, None);
+        assert_eq!(sys.stcspr.balance_of(owner), U256::from(4_000 * CSPR));
+        sys.tm.add_collateral(U256::from(2_000 * CSPR));
+        assert_eq!(sys.stcspr.balance_of(owner), U256::from(2_000 * CSPR));
+        sys.tm.withdraw_collateral(U256::from(3_000 * CSPR));
+        assert_eq!(sys.stcspr.balance_of(owner), U256::from(5_000 * CSPR));
+        assert_eq!(sys.tm.get_collateral_token_balance(), held + U256::from(5_000 * CSPR));
+
+        // Closing hands back the rest, once the fee is bought and repaid
+        sys.env.set_caller(other);
+        sys.cusd.transfer(owner, U256::from(CSPR));
+        sys.env.set_caller(owner);
+        sys.tm.repay(sys.tm.get_trove_debt(owner));
+        sys.tm.close_trove();
+        assert_eq!(sys.stcspr.balance_of(owner), U256::from(10_000 * CSPR));
+        assert_eq!(sys.tm.get_collateral_token_balance(), held);
+        assert_eq!(sys.tm.get_total_collateral(), held);
+    }
+
+    #[test]
+    fn borrowing_mints_and_repaying_burns() {
+        let mut sys = setup();
+        let owner = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        assert_eq!(sys.cusd.balance_of(owner), U256::from(10_000 * CSPR));
+
+        sys.tm.borrow(U256::from(1_000 * CSPR), None, None);
+        assert_eq!(sys.cusd.balance_of(owner), U256::from(11_000 * CSPR));
+        assert_eq!(sys.tm.get_trove_debt(owner), U256::from(11_055 * CSPR));
+
+        sys.tm.repay(U256::from(4_000 * CSPR));
+        assert_eq!(sys.cusd.balance_of(owner), U256::from(7_000 * CSPR));
+        assert_eq!(sys.tm.get_trove_debt(owner), U256::from(7_055 * CSPR));
+        // The borrowing fees are the only debt not backed by minted cUSD
+        assert_eq!(sys.cusd.total_supply(), U256::from(7_000 * CSPR));
+        assert_eq!(sys.tm.get_outstanding_fees(), U256::from(55 * CSPR));
+        assert!(sys.tm.is_supply_reconciled());
+    }
+
+    #[test]
+    fn failures_revert_with_typed_errors() {
+        let mut sys = setup();
+        let owner = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let stranger = sys.env.get_account(2);
+        sys.env.set_caller(stranger);
+        assert_eq!(sys.tm.try_set_treasury(stranger), Err(Error::NotOwner.into()));
+        assert_eq!(sys.tm.try_repay(U256::from(CSPR)), Err(Error::NoActiveTrove.into()));
+        assert_eq!(sys.tm.try_liquidate(owner).unwrap_err().code(), 313);
+
+        sys.env.set_caller(owner);
+        assert_eq!(
+            sys.tm.try_open_trove(U256::from(CSPR), U256::from(MIN_DEBT), MIN_INTEREST_RATE, None, None),
+            Err(Error::TroveAlreadyExists.into())
+        );
+        assert_eq!(sys.tm.try_close_trove(), Err(Error::OutstandingDebt.into()));
+        assert_eq!(sys.tm.try_adjust_interest_rate(1, None, None), Err(Error::InterestRateTooLow.into()));
+        assert_eq!(
+            sys.tm.try_borrow(U256::from(100_000 * CSPR), None, None),
+            Err(Error::BelowMinCollateralRatio.into())
+        );
+        assert_eq!(
+            sys.pool.try_offset(U256::one(), U256::one(), U256::one()),
+            Err(crate::stability_pool::Error::NotTroveManager.into())
+        );
+    }
+
+    #[test]
+    fn large_trove_accrues_interest_without_overflow() {
+        let mut sys = setup();
+        // $50,000 of collateral at $0.05, borrowing 20,000 cUSD at 100% APR
+        let debt = U256::from(20_000 * CSPR);
+        let owner = open_large_trove(&mut sys, 1, debt, DECIMALS);
+        assert_eq!(sys.tm.get_trove_collateral(owner), U256::from(1_000_000 * CSPR));
+        let fee = debt * BORROWING_FEE / DECIMALS;
+        assert!(sys.env.emitted_event(&sys.tm, TroveOpened {
+            owner,
+            collateral: U256::from(1_000_000 * CSPR),
+            debt: debt + fee,
+            interest_rate: DECIMALS,
+            fee,
+        }));
+
+        let price = sys.oracle.get_price();
+        sys.env.advance_block_time(SECONDS_PER_YEAR * 1000);
+        feed_price(&mut sys, price);
+
+        sys.env.set_caller(owner);
+        sys.tm.repay(U256::from(CSPR));
+        let trove = sys.tm.get_trove(owner);
+        assert!(sys.env.emitted_event(&sys.tm, TroveAdjusted {
+            owner,
+            collateral: trove.collateral,
+            debt: trove.debt,
+            interest_rate: DECIMALS,
+        }));
+        // One year at 100% doubles the debt (fee included)
+        let expected = (debt + fee) * 2u64 - U256::from(CSPR);
+        assert!(trove.debt >= expected - U256::from(CSPR) && trove.debt <= expected + U256::from(CSPR));
+        assert!(sys.tm.is_supply_reconciled());
+    }
+
+    #[test]
+    fn total_debt_includes_interest_of_untouched_troves() {
+        let mut sys = setup();
+        let debt = U256::from(10_000 * CSPR);
+        let first = open_large_trove(&mut sys, 1, debt, DECIMALS / 10);
+        let second = open_large_trove(&mut sys, 2, debt, DECIMALS / 5);
+        let recorded = sys.tm.get_total_debt();
+
+        let price = sys.oracle.get_price();
+        sys.env.advance_block_time(SECONDS_PER_YEAR * 1000);
+        feed_price(&mut sys, price);
+
+        // 10% and 20% on 10,050 cUSD each, without either trove being touched
+        let interest = recorded / 2u64 / 10u64 + recorded / 2u64 / 5u64;
+        let total = sys.tm.get_total_debt();
+        assert!(total >= recorded + interest && total <= recorded + interest + 1);
+        assert_eq!(sys.tm.get_outstanding_fees(), total - sys.cusd.total_supply());
+
+        // Syncing the troves records the same interest, never more than the aggregate
+        sys.env.set_caller(first);
+        sys.tm.add_collateral(U256::zero());
+        sys.env.set_caller(second);
+        sys.tm.add_collateral(U256::zero());
+        let troves = sys.tm.get_trove_debt(first) + sys.tm.get_trove_debt(second);
+        assert_eq!(sys.tm.get_total_debt(), total);
+        assert!(troves <= total && total - troves <= U256::from(2));
+        assert!(sys.tm.is_supply_reconciled());
+    }
+
+    #[test]
+    fn minted_interest_is_split_between_pool_and_treasury() {
+        let mut sys = setup();
+        let treasury = sys.env.get_account(6);
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.tm.set_treasury(treasury);
+
+        let depositor = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), DECIMALS / 10);
+        sys.env.set_caller(depositor);
+        let deposit = U256::from(10_000 * CSPR);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+
+        let price = sys.oracle.get_price();
+        sys.env.advance_block_time(SECONDS_PER_YEAR * 1000);
+        feed_price(&mut sys, price);
+
+        // 10% on 20,100 cUSD: 75% to depositors, 25% to the treasury
+        let interest = U256::from(2_010 * CSPR);
+        assert_eq!(sys.tm.get_total_debt(), U256::from(20_100 * CSPR) + interest);
+        sys.tm.mint_interest();
+
+        let to_pool = U256::from(1_507_500_000_000u64);
+        assert_eq!(sys.pool.get_interest_balance(), to_pool);
+        assert_eq!(sys.cusd.balance_of(treasury), interest - to_pool);
+        assert_eq!(sys.tm.get_outstanding_fees(), U256::from(100 * CSPR));
+        assert!(sys.tm.is_supply_reconciled());
+
+        // The only depositor takes all of it
+        let before = sys.cusd.balance_of(depositor);
+        sys.env.set_caller(depositor);
+        assert_eq!(sys.pool.get_pending_interest_gain(depositor), to_pool);
+        sys.pool.claim_rewards();
+        assert_eq!(sys.cusd.balance_of(depositor) - before, to_pool);
+        assert!(sys.pool.get_interest_balance().is_zero());
+        assert_eq!(sys.pool.get_deposit(depositor), deposit);
+    }
+
+    #[test]
+    fn newcomers_get_no_share_of_earlier_interest() {
+        let mut sys = setup();
+        let depositor = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), DECIMALS / 10);
+        let newcomer = sys.env.get_account(2);
+        let deposit = U256::from(10_000 * CSPR);
+        sys.env.set_caller(depositor);
+        sys.cusd.transfer(newcomer, deposit);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+
+        let price = sys.oracle.get_price();
+        sys.env.advance_block_time(SECONDS_PER_YEAR * 1000);
+        feed_price(&mut sys, price);
+
+        // The year's interest is minted before the newcomer's deposit lands
+        sys.env.set_caller(newcomer);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+        sys.tm.mint_interest();
+
+        let to_pool = U256::from(1_507_500_000_000u64);
+        assert!(sys.pool.get_pending_interest_gain(newcomer).is_zero());
+        assert_eq!(sys.pool.get_pending_interest_gain(depositor), to_pool);
+        assert_eq!(sys.pool.get_interest_balance(), to_pool);
+        assert!(sys.tm.is_supply_reconciled());
+    }
+
+    #[test]
+    fn recovery_mode_guards_collateral_and_new_troves() {
+        let mut sys = setup();
+        // $50,000 against 33,165 cUSD (fee included): a TCR of ~150.8%
+        let whale = open_large_trove(&mut sys, 1, U256::from(33_000 * CSPR), MIN_INTEREST_RATE);
+        let saver = sys.env.get_account(2);
+        sys.env.set_caller(saver);
+        sys.stcspr.faucet();
+        sys.stcspr.approve(sys.tm.address(), U256::from(10_000 * CSPR));
+        sys.tm.open_trove(U256::from(10_000 * CSPR), U256::from(100 * CSPR), MIN_INTEREST_RATE, None, None);
+        
+        // Repay the saver's debt in full, fee included
+        let owed = sys.tm.get_trove_debt(saver);
+        sys.env.set_caller(whale);
+        sys.cusd.transfer(saver, owed - U256::from(100 * CSPR));
+        sys.env.set_caller(saver);
+        sys.tm.repay(owed);
+        assert!(sys.tm.get_trove_debt(saver).is_zero());
+        
+        move_price(&mut sys, U256::from(49_000_000));
+        assert!(sys.tm.is_recovery_mode());
+        
+        // Debt-free or not, collateral stays put in recovery mode
+        sys.env.set_caller(saver);
+        assert_eq!(sys.tm.try_withdraw_collateral(U256::from(CSPR)), Err(Error::NotAllowedInRecoveryMode.into()));
+        assert_eq!(sys.tm.try_close_trove(), Err(Error::NotAllowedInRecoveryMode.into()));
+        
+        // 32,600 cUSD on $49,000 clears the 150% MCR, but not once the fee is added
+        let borrower = sys.env.get_account(3);
+        sys.env.set_caller(borrower);
+        for _ in 0..FAUCET_CALLS {
+            sys.stcspr.faucet();
+        }
+        let collateral = sys.stcspr.balance_of(borrower);
+        sys.stcspr.approve(sys.tm.address(), collateral);
+        assert_eq!(
+            sys.tm.try_open_trove(collateral, U256::from(32_600 * CSPR), MIN_INTEREST_RATE, None, None),
+            Err(Error::BelowCriticalRatio.into())
+        );
+        sys.tm.open_trove(collateral, U256::from(32_000 * CSPR), MIN_INTEREST_RATE, None, None);
+        assert!(sys.tm.get_trove_active(borrower));
+    }
+
+    #[test]
+    fn large_liquidation_offsets_against_pool() {
+        let mut sys = setup();
+        let depositor = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let risky = open_large_trove(&mut sys, 2, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+
+        sys.env.set_caller(depositor);
+        let deposit = U256::from(10_000 * CSPR);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+
+        // $0.05 -> $0.032 puts the risky trove at ~106%, the system stays above 150%
+        move_price(&mut sys, U256::from(32_000_000));
+        assert!(sys.tm.is_liquidatable(risky));
+        let trove = sys.tm.get_trove(risky);
+
+        let liquidator = sys.env.get_account(3);
+        sys.env.set_caller(liquidator);
+        sys.tm.liquidate(risky);
+        assert!(sys.env.emitted_event(&sys.tm, TroveLiquidated {
+            owner: risky,
+            liquidator,
+            debt: trove.debt,
+            collateral: trove.collateral,
+            debt_offset: deposit,
+            debt_redistributed: trove.debt - deposit,
+            collateral_surplus: U256::zero(),
+        }));
+
+        assert!(!sys.tm.get_trove_active(risky));
+        assert!(sys.pool.get_total_deposits().is_zero());
+        assert!(sys.pool.get_pending_collateral_gain(depositor) > U256::from(300_000 * CSPR));
+        assert_eq!(sys.tm.get_total_collateral(), sys.tm.get_collateral_token_balance());
+    }
+
+    #[test]
+    fn liquidation_burns_the_offset_debt_from_the_pool() {
+        let mut sys = setup();
+        let depositor = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let risky = open_large_trove(&mut sys, 2, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        sys.env.set_caller(depositor);
+        let deposit = U256::from(10_000 * CSPR);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+
+        move_price(&mut sys, U256::from(32_000_000));
+        let trove = sys.tm.get_trove(risky);
+        let supply = sys.cusd.total_supply();
+        let total_debt = sys.tm.get_total_debt();
+        sys.env.set_caller(sys.env.get_account(3));
+        sys.tm.liquidate(risky);
+
+        // The pool's cUSD is burned against the debt, and it gets the
+        // matching share of the collateral left after gas compensation
+        let to_pool = (trove.collateral - trove.collateral / 200u64) * deposit / trove.debt;
+        assert!(sys.cusd.balance_of(sys.pool.address()).is_zero());
+        assert_eq!(sys.cusd.total_supply(), supply - deposit);
+        assert_eq!(sys.tm.get_total_debt(), total_debt - deposit);
+        assert_eq!(sys.stcspr.balance_of(sys.pool.address()), to_pool);
+        assert_eq!(sys.pool.get_collateral_balance(), to_pool);
+    }
+
+    #[test]
+    fn batch_liquidation_settles_one_offset() {
+        let mut sys = setup();
+        let depositor = open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let first = open_large_trove(&mut sys, 2, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        let second = open_large_trove(&mut sys, 3, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        let healthy = open_large_trove(&mut sys, 4, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+
+        sys.env.set_caller(depositor);
+        let deposit = U256::from(10_000 * CSPR);
+        sys.cusd.approve(sys.pool.address(), deposit);
+        sys.pool.deposit(deposit);
+
+        move_price(&mut sys, U256::from(32_000_000));
+        let events = sys.env.events_count(&sys.pool);
+
+        let keeper = sys.env.get_account(5);
+        sys.env.set_caller(keeper);
+        let totals = sys.tm.batch_liquidate(vec![first, healthy, second, first]);
+
+        assert_eq!(totals.troves, 2);
+        assert_eq!(totals.debt_offset, deposit);
+        assert_eq!(totals.debt, totals.debt_offset + totals.debt_redistributed);
+        assert_eq!(sys.env.events_count(&sys.pool), events + 1);
+        assert!(!sys.tm.get_trove_active(first));
+        assert!(!sys.tm.get_trove_active(second));
+        assert!(sys.tm.get_trove_active(healthy));
+        assert_eq!(sys.stcspr.balance_of(keeper), totals.gas_compensation);
+        assert_eq!(sys.tm.get_total_collateral(), sys.tm.get_collateral_token_balance());
+
+        assert_eq!(sys.tm.try_batch_liquidate(vec![healthy]), Err(Error::NotLiquidatable.into()));
+    }
+
+    #[test]
+    fn batch_skips_the_last_staked_trove() {
+        let mut sys = setup();
+        let first = open_large_trove(&mut sys, 1, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        let last = open_large_trove(&mut sys, 2, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        move_price(&mut sys, U256::from(32_000_000));
+
+        // Empty pool: the first trove is redistributed onto the last,
+        // which then has no other stake to pass its own debt to
+        sys.env.set_caller(sys.env.get_account(5));
+        let totals = sys.tm.batch_liquidate(vec![first, last]);
+        assert_eq!(totals.troves, 1);
+        assert_eq!(totals.debt_redistributed, totals.debt);
+        assert!(!sys.tm.get_trove_active(first));
+        assert!(sys.tm.get_trove_active(last));
+        assert_eq!(sys.tm.try_liquidate(last), Err(Error::OnlyOneTrove.into()));
+        assert_eq!(sys.tm.get_total_collateral(), sys.tm.get_collateral_token_balance());
+    }
+
+    #[test]
+    fn later_troves_share_only_later_redistributions() {
+        let mut sys = setup();
+        let first = open_large_trove(&mut sys, 1, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        let second = open_large_trove(&mut sys, 2, U256::from(20_000 * CSPR), MIN_INTEREST_RATE);
+        let older = open_large_trove(&mut sys, 3, U256::from(5_000 * CSPR), MIN_INTEREST_RATE);
+        open_large_trove(&mut sys, 4, U256::from(5_000 * CSPR), MIN_INTEREST_RATE);
+
+        // Empty pool: the first trove is spread over the other three
+        move_price(&mut sys, U256::from(32_000_000));
+        sys.env.set_caller(sys.env.get_account(5));
+        sys.tm.liquidate(first);
+        let (earlier_gain, _) = sys.tm.get_pending_rewards(older);
+
+        // A newcomer with as much collateral as the older trove now holds
+        let newcomer = sys.env.get_account(6);
+        let collateral = sys.tm.get_trove(older).collateral;
+        sys.env.set_caller(newcomer);
+        for _ in 0..140 {
+            sys.stcspr.faucet();
+        }
+        sys.stcspr.approve(sys.tm.address(), collateral);
+        sys.tm.open_trove(collateral, U256::from(5_000 * CSPR), MIN_INTEREST_RATE, None, None);
+        assert!(sys.tm.get_trove(newcomer).stake < collateral);
+
+        // Both get the same share of the second redistribution
+        move_price(&mut sys, U256::from(24_500_000));
+        sys.env.set_caller(sys.env.get_account(5));
+        sys.tm.liquidate(second);
+        let (older_gain, _) = sys.tm.get_pending_rewards(older);
+        let (newcomer_gain, _) = sys.tm.get_pending_rewards(newcomer);
+        assert!(newcomer_gain > U256::zero());
+        let later_gain = older_gain - earlier_gain;
+        assert!(newcomer_gain <= later_gain && later_gain - newcomer_gain <= U256::from(2));
+        assert_eq!(sys.tm.get_total_collateral(), sys.tm.get_collateral_token_balance());
+    }
+
+    #[test]
+    fn liquidate_troves_takes_the_riskiest_first() {
+        let mut sys = setup();
+        open_large_trove(&mut sys, 1, U256::from(10_000 * CSPR), MIN_INTEREST_RATE);
+        let risky = open_large_trove(&mut sys, 2, U256::from(30_000 * CSPR), 20_000_000);
+        let riskiest = open_large_trove(&mut sys, 3, U256::from(32_000 * CSPR), 30_000_000);
+
+        // $32,000 of collateral each: ICRs of ~106% and ~99.5%
+        move_price(&mut sys, U256::from(32_000_000));
+        sys.env.set_caller(sys.env.get_account(5));
+        let totals = sys.tm.liquidate_troves(1);
+
+        // The riskiest trove goes first, though it's further down the list
+        assert_eq!(totals.troves, 1);
+        assert!(totals.debt_offset.is_zero());
+        assert!(!sys.tm.get_trove_active(riskiest));
+        assert!(sys.tm.get_trove_active(risky));
+
+        sys.tm.liquidate_troves(10);
+        assert!(!sys.tm.get_trove_active(risky));
+        assert_eq!(sys.tm.get_trove_count(), 1);
+        assert_eq!(sys.tm.try_liquidate_troves(10), Err(Error::NotLiquidatable.into()));
+    }
+
+    #[test]
+    fn large_redemption_draws_collateral_at_face_value() {
+        let mut sys = setup();
+        let borrower = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), MIN_INTEREST_RATE);
+        let redeemer = open_large_trove(&mut sys, 2, U256::from(20_000 * CSPR), 100_000_000);
+
+        sys.env.set_caller(redeemer);
+        let before = sys.stcspr.balance_of(redeemer);
+        sys.tm.redeem_collateral(U256::from(10_000 * CSPR), DECIMALS, 0);
+
+        // 10,000 cUSD at $0.05 is 200,000 stCSPR before the fee
+        let redeemed = U256::from(200_000 * CSPR);
+        let fee_rate = sys.tm.get_redemption_fee();
+        let fee = redeemed * fee_rate / DECIMALS;
+        assert_eq!(sys.stcspr.balance_of(redeemer) - before, redeemed - fee);
+        assert!(sys.env.emitted_event(&sys.tm, Redemption {
+            redeemer,
+            cusd_amount: U256::from(10_000 * CSPR),
+            debt_redeemed: U256::from(10_000 * CSPR),
+            collateral_drawn: redeemed - fee,
+            fee_rate,
+        }));
+        assert!(sys.tm.get_trove_debt(borrower) < U256::from(20_000 * CSPR));
+        assert_eq!(sys.tm.get_total_collateral(), sys.tm.get_collateral_token_balance());
+    }
+
+    #[test]
+    fn short_redemption_sets_the_fee_by_the_debt_redeemed() {
+        let mut sys = setup();
+        let risky = open_large_trove(&mut sys, 1, U256::from(30_000 * CSPR), MIN_INTEREST_RATE);
+        let redeemer = open_large_trove(&mut sys, 2, U256::from(20_000 * CSPR), 100_000_000);
+        sys.env.set_caller(risky);
+        sys.cusd.transfer(redeemer, U256::from(30_000 * CSPR));
+        move_price(&mut sys, U256::from(32_000_000));
+        
+        // The risky trove is left for liquidators, so only the redeemer's
+        // own 20,100 cUSD of debt can be redeemed out of the 50,000 asked for
+        let supply = sys.cusd.total_supply();
+        sys.env.set_caller(redeemer);
+        sys.tm.redeem_collateral(U256::from(50_000 * CSPR), DECIMALS, 0);
+        
+        let redeemed = U256::from(20_100 * CSPR);
+        assert!(sys.tm.get_trove_debt(redeemer).is_zero());
+        assert_eq!(sys.cusd.balance_of(redeemer), U256::from(50_000 * CSPR) - redeemed);
+        let fraction = Decimal::from_ratio(redeemed, supply, Rounding::Up).unwrap().raw_u64();
+        assert_eq!(sys.tm.get_redemption_fee(), fraction / REDEMPTION_BETA);
+    }
+
+    #[test]
+    fn redemption_stops_after_max_iterations() {
+        let mut sys = setup();
+        let first = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), MIN_INTEREST_RATE);
+        let second = open_large_trove(&mut sys, 2, U256::from(20_000 * CSPR), 20_000_000);
+        let redeemer = open_large_trove(&mut sys, 3, U256::from(20_000 * CSPR), 100_000_000);
+        sys.env.set_caller(first);
+        sys.cusd.transfer(redeemer, U256::from(10_000 * CSPR));
+
+        // Only the first trove is visited, so 20,100 of the 30,000 is redeemed
+        sys.env.set_caller(redeemer);
+        sys.tm.redeem_collateral(U256::from(30_000 * CSPR), DECIMALS, 1);
+        let debt = U256::from(20_100 * CSPR);
+        assert!(sys.tm.get_trove_debt(first).is_zero());
+        assert_eq!(sys.tm.get_trove_debt(second), debt);
+        assert_eq!(sys.cusd.balance_of(redeemer), U256::from(30_000 * CSPR) - debt);
+
+        // The zombie is out of the list, the next iteration reaches the second trove
+        sys.tm.redeem_collateral(U256::from(CSPR), DECIMALS, 1);
+        assert_eq!(sys.tm.get_trove_debt(second), debt - U256::from(CSPR));
+        assert_eq!(sys.tm.get_trove_debt(redeemer), debt);
+    }
+
+    #[test]
+    fn borrowing_revives_a_zombie_at_its_hinted_position() {
+        let mut sys = setup();
+        let zombie = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), MIN_INTEREST_RATE);
+        let middle = open_large_trove(&mut sys, 2, U256::from(20_000 * CSPR), 20_000_000);
+        let redeemer = open_large_trove(&mut sys, 3, U256::from(20_000 * CSPR), 100_000_000);
+        sys.env.set_caller(zombie);
+        sys.cusd.transfer(redeemer, U256::from(10_000 * CSPR));
+        sys.env.set_caller(redeemer);
+        sys.tm.redeem_collateral(U256::from(20_100 * CSPR), DECIMALS, 1);
+        assert_eq!(sys.tm.get_troves(None, 10), vec![middle, redeemer]);
+
+        // Hints from the view go straight in, borrowing back above the minimum
+        let (prev, next) = sys.tm.find_insert_position(MIN_INTEREST_RATE, None, None);
+        assert_eq!((prev, next), (None, Some(middle)));
+        sys.env.set_caller(zombie);
+        sys.tm.borrow(U256::from(1_000 * CSPR), prev, next);
+        assert_eq!(sys.tm.get_troves(None, 10), vec![zombie, middle, redeemer]);
+        assert!(!sys.tm.get_trove(zombie).zombie);
+    }
+
+    #[test]
+    fn frozen_oracles_halt_price_dependent_operations() {
+        let mut sys = setup();
+        let owner = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), DECIMALS);
+        let crash = U256::from(35_000_000);
+        
+        // A 30% crash trips the primary's circuit breaker - the secondary takes over
+        feed(&sys.env, &mut sys.oracle, crash);
+        assert!(sys.oracle.is_frozen());
+        assert!(!sys.tm.is_recovery_mode());
+        sys.env.set_caller(owner);
+        sys.tm.borrow(U256::from(CSPR), None, None);
+        
+        // With both frozen there's no price to trust
+        feed(&sys.env, &mut sys.secondary, crash);
+        assert_eq!(sys.tm.try_is_recovery_mode(), Err(Error::PriceUnavailable.into()));
+        sys.env.set_caller(owner);
+        assert_eq!(sys.tm.try_borrow(U256::from(CSPR), None, None), Err(Error::PriceUnavailable.into()));
+    }
+
+    #[test]
+    fn staking_rewards_raise_collateral_value() {
+        let mut sys = setup();
+        let owner = open_large_trove(&mut sys, 1, U256::from(20_000 * CSPR), DECIMALS);
+        let before = sys.tm.get_collateral_ratio(owner);
+        
+        // 1,000,000 stCSPR earn 100,000 CSPR of rewards
+        sys.env.set_caller(sys.env.get_account(0));
+        sys.stcspr.accrue_rewards(U256::from(100_000 * CSPR));
+        
+        assert_eq!(sys.stcspr.exchange_rate(), U256::from(1_100_000_000));
+        // $50,000 -> $55,000 against 20,100 cUSD of debt (incl. fee)
+        assert_eq!(before, U256::from(248));
+        assert_eq!(sys.tm.get_collateral_ratio(owner), U256::from(273));
     }
 }
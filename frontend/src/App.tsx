@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from 'react'
-import { CasperClient, DeployUtil, RuntimeArgs, CLPublicKey, CLValueBuilder } from 'casper-js-sdk'
+import { CasperClient, DeployUtil, RuntimeArgs, CLPublicKey, CLValueBuilder, CLTypeBuilder } from 'casper-js-sdk'
+import { None } from 'ts-results'
 
 // Contract hashes - UPDATE AFTER DEPLOYMENT
 const CONTRACTS = {
@@ -185,7 +186,7 @@ function App() {
             spender: CLValueBuilder.key(
               CLValueBuilder.byteArray(Buffer.from(CONTRACTS.troveManager.replace('hash-', ''), 'hex'))
             ),
-            amount: CLValueBuilder.u64(collateralAmount),
+            amount: CLValueBuilder.u256(collateralAmount),
           })
         ),
         DeployUtil.standardPayment(2_000_000_000)
@@ -203,9 +204,12 @@ function App() {
           Uint8Array.from(Buffer.from(CONTRACTS.troveManager.replace('hash-', ''), 'hex')),
           'open_trove',
           RuntimeArgs.fromMap({
-            collateral: CLValueBuilder.u64(collateralAmount),
-            debt: CLValueBuilder.u64(borrowAmount),
+            collateral: CLValueBuilder.u256(collateralAmount),
+            debt: CLValueBuilder.u256(borrowAmount),
             interest_rate: CLValueBuilder.u64(rate),
+            // No sorted-list hints: the contract walks the list itself
+            prev_hint: CLValueBuilder.option(None, CLTypeBuilder.key()),
+            next_hint: CLValueBuilder.option(None, CLTypeBuilder.key()),
           })
         ),
         DeployUtil.standardPayment(5_000_000_000)
@@ -247,7 +251,7 @@ function App() {
             spender: CLValueBuilder.key(
               CLValueBuilder.byteArray(Buffer.from(CONTRACTS.stabilityPool.replace('hash-', ''), 'hex'))
             ),
-            amount: CLValueBuilder.u64(amount),
+            amount: CLValueBuilder.u256(amount),
           })
         ),
         DeployUtil.standardPayment(2_000_000_000)
@@ -263,7 +267,7 @@ function App() {
           Uint8Array.from(Buffer.from(CONTRACTS.stabilityPool.replace('hash-', ''), 'hex')),
           'deposit',
           RuntimeArgs.fromMap({
-            amount: CLValueBuilder.u64(amount),
+            amount: CLValueBuilder.u256(amount),
           })
         ),
         DeployUtil.standardPayment(3_000_000_000)